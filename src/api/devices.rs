@@ -1,34 +1,160 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     Json,
 };
-use serde::Deserialize;
-use tracing::info;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
 use crate::AppState;
-use crate::models::device::AudioDevice;
+use crate::api::REVISION_HEADER;
+use crate::audio::controller::UNITY_VOLUME;
+use crate::models::device::{AudioDevice, VolumeChange, VolumeScale};
+use crate::utils::broadcast::ServerEvent;
 
 pub async fn list_devices(
     State(state): State<AppState>,
-) -> Json<Vec<AudioDevice>> {
+) -> ([(&'static str, String); 1], Json<Vec<AudioDevice>>) {
     let audio = state.audio.read();
-    Json(audio.list_devices())
+    ([(REVISION_HEADER, audio.revision().to_string())], Json(audio.list_devices()))
+}
+
+pub async fn get_device(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> Result<Json<AudioDevice>, StatusCode> {
+    state.audio.read().get_device(id).cloned().map(Json).ok_or(StatusCode::NOT_FOUND)
 }
 
 #[derive(Deserialize)]
 pub struct SetVolumeRequest {
-    pub volume: f32,
+    /// `0.5`, `"50%"`, or relative: `"+5%"`, `"-3 dB"`. Plain numbers and
+    /// steps are read in `scale`; a `dB` suffix is always a decibel step.
+    /// With `scale=db`, `"-6"` is the level −6 dB.
+    pub volume: VolumeChange,
+    #[serde(default)]
+    pub scale: VolumeScale,
     pub timestamp: Option<u64>,
 }
 
+#[derive(Deserialize)]
+pub struct ScaleQuery {
+    #[serde(default)]
+    pub scale: VolumeScale,
+}
+
+/// A volume and the device's upper limit, both in `scale`.
+#[derive(Serialize)]
+pub struct VolumeState {
+    pub volume: f32,
+    pub max: f32,
+    pub scale: VolumeScale,
+}
+
+impl VolumeState {
+    pub fn new(volume: f32, max: f32, scale: VolumeScale) -> Self {
+        Self {
+            volume: scale.express(volume),
+            max: scale.express(max),
+            scale,
+        }
+    }
+}
+
+/// A device's current volume, e.g. `?scale=db`.
+pub async fn get_volume(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+    Query(query): Query<ScaleQuery>,
+) -> Result<Json<VolumeState>, StatusCode> {
+    let audio = state.audio.read();
+    let (Some(volume), Some(max)) = (audio.volume(id), audio.max_volume(id)) else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    Ok(Json(VolumeState::new(volume, max, query.scale)))
+}
+
+/// Sets a device's volume, clamped to the allowed range. Replies with the
+/// resulting absolute volume in the request's scale.
 pub async fn set_volume(
     State(state): State<AppState>,
     Path(id): Path<u32>,
     Json(payload): Json<SetVolumeRequest>,
-) -> StatusCode {
-    info!("API Request: Set volume for device {} to {:.2}", id, payload.volume);
+) -> Result<Json<VolumeState>, StatusCode> {
+    let (volume, max) = {
+        let mut audio = state.audio.write();
+        let volume = audio.request_volume(id, payload.volume, payload.scale).ok_or(StatusCode::NOT_FOUND)?;
+        (volume, audio.max_volume(id).ok_or(StatusCode::NOT_FOUND)?)
+    };
+    info!("API Request: Set volume for device {} to {:.2}", id, volume);
     // 1. Command PipeWire
-    state.pw_handler.set_volume(id, payload.volume, payload.timestamp);
-    
-    StatusCode::OK
+    state.pw_handler.set_volume(id, volume, payload.timestamp);
+
+    Ok(Json(VolumeState::new(volume, max, payload.scale)))
+}
+
+#[derive(Deserialize)]
+pub struct SetBoostRequest {
+    pub enabled: bool,
+}
+
+#[derive(Serialize)]
+pub struct BoostState {
+    pub boost: bool,
+}
+
+/// Allows or forbids volumes above 100% for a device, remembered by node
+/// name. Disabling boost brings a louder device back down to 100%.
+pub async fn set_boost(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+    Json(payload): Json<SetBoostRequest>,
+) -> Result<Json<BoostState>, StatusCode> {
+    let boost = payload.enabled;
+    // Same lock order as the event loop, so the event's revision matches
+    let mut audio = state.audio.write();
+    let device = audio.get_device(id).ok_or(StatusCode::NOT_FOUND)?;
+    let (name, volume) = (device.name.clone(), device.base_volume);
+    let changed = state.boost.set(&name, boost).map_err(|e| {
+        error!("Failed to save boost setting: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if changed {
+        info!("API Request: Set boost for device {} to {}", id, boost);
+        audio.set_boost(id, boost);
+        let mut graph = state.graph.write();
+        let revision = state.broadcaster.send(ServerEvent::BoostChanged { id, boost });
+        audio.set_revision(revision);
+        graph.set_revision(revision);
+    }
+    if !boost && volume > UNITY_VOLUME {
+        state.pw_handler.set_volume(id, UNITY_VOLUME, None);
+    }
+    Ok(Json(BoostState { boost }))
+}
+
+#[derive(Deserialize)]
+pub struct SetMuteRequest {
+    /// Omit to toggle the current state.
+    pub muted: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct MuteState {
+    pub muted: bool,
+}
+
+/// Mutes or unmutes a device, or toggles it when `muted` or the whole body
+/// is omitted.
+/// Replies with the state requested; clients see the change applied as a
+/// `MuteChanged` event.
+pub async fn set_mute(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+    payload: Option<Json<SetMuteRequest>>,
+) -> Result<Json<MuteState>, StatusCode> {
+    let current = state.audio.read().get_device(id).map(|d| d.muted).ok_or(StatusCode::NOT_FOUND)?;
+    let muted = payload.and_then(|Json(p)| p.muted).unwrap_or(!current);
+    info!("API Request: Set mute for device {} to {}", id, muted);
+    state.pw_handler.set_mute(id, muted);
+    Ok(Json(MuteState { muted }))
 }
@@ -0,0 +1,121 @@
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+};
+use crate::AppState;
+use crate::api::rpc::Session;
+use crate::api::websocket::{open_session, snapshot, subscribe};
+use crate::utils::broadcast::{ServerEvent, Stamped};
+use futures::stream;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{info, warn};
+
+#[derive(Deserialize)]
+pub struct EventsParams {
+    /// Last revision the client has applied; `Last-Event-ID` takes precedence.
+    pub since: Option<u64>,
+    pub topics: Option<String>,
+    pub log_level: Option<String>,
+    pub meters: Option<String>,
+}
+
+struct Feed {
+    state: AppState,
+    rx: broadcast::Receiver<Stamped>,
+    meter_rx: broadcast::Receiver<ServerEvent>,
+    session: Session,
+    backlog: VecDeque<Stamped>,
+    // Events at or below this were already covered by a resync snapshot
+    skip_through: u64,
+}
+
+/// The `/ws` event feed as Server-Sent Events, for clients that can't speak
+/// WebSocket. Each event's `data` is the same JSON as a WebSocket frame and
+/// its `id` is the revision, so a reconnecting `EventSource` resumes through
+/// `Last-Event-ID`; a resume too old for the history gets a snapshot
+/// instead, unless the topics leave out every state topic. Commands aren't
+/// accepted; use REST for those.
+pub async fn handler(
+    State(state): State<AppState>,
+    Query(params): Query<EventsParams>,
+    headers: HeaderMap,
+) -> Response {
+    let session = match open_session(&state, &params.topics, &params.log_level, &params.meters) {
+        Ok(session) => session,
+        Err(status) => return status.into_response(),
+    };
+    let since = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+        .or(params.since);
+
+    let (rx, catchup) = subscribe(&state, since);
+    info!("SSE client subscribed (since {:?}, {} catch-up messages, topics {:?})",
+        since, catchup.len(), session.filter.topics);
+
+    let meter_rx = state.broadcaster.subscribe_meters();
+    let feed = Feed {
+        state,
+        rx,
+        meter_rx,
+        session,
+        backlog: catchup.into(),
+        skip_through: 0,
+    };
+    let events = stream::unfold(feed, |mut feed| async move {
+        let event = match feed.next().await? {
+            Outgoing::Stamped(stamped) => Event::default().id(stamped.revision.to_string()).json_data(&stamped),
+            // Meter frames carry no revision, so they leave `Last-Event-ID` alone
+            Outgoing::Meter(meter) => Event::default().json_data(&meter),
+        };
+        Some((event, feed))
+    });
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+enum Outgoing {
+    Stamped(Stamped),
+    Meter(ServerEvent),
+}
+
+impl Feed {
+    async fn next(&mut self) -> Option<Outgoing> {
+        loop {
+            if let Some(stamped) = self.backlog.pop_front() {
+                if self.session.filter.matches(&stamped.event) {
+                    return Some(Outgoing::Stamped(stamped));
+                }
+                continue;
+            }
+            tokio::select! {
+                event = self.rx.recv() => match event {
+                    Ok(stamped) if !self.session.filter.matches(&stamped.event) => continue,
+                    Ok(stamped) if stamped.revision <= self.skip_through => continue,
+                    Ok(stamped) => return Some(Outgoing::Stamped(stamped)),
+                    Err(RecvError::Lagged(n)) => {
+                        if !self.session.filter.has_state() {
+                            continue;
+                        }
+                        warn!("SSE client lagged by {} messages, resending state", n);
+                        let snapshot = snapshot(&self.state);
+                        self.skip_through = snapshot.revision;
+                        return Some(Outgoing::Stamped(snapshot));
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+                frame = self.meter_rx.recv() => match frame {
+                    Ok(event) if self.session.filter.matches(&event) => return Some(Outgoing::Meter(event)),
+                    Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                },
+            }
+        }
+    }
+}
@@ -1,18 +1,104 @@
 use axum::{
-    extract::State,
+    extract::{Path, Query, State},
     Json,
-    http::StatusCode,
+    http::{header, StatusCode},
 };
-use serde::Deserialize;
-use tracing::info;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use std::collections::HashSet;
+use std::sync::Arc;
 use crate::AppState;
-use crate::models::graph::AudioGraph;
+use crate::api::REVISION_HEADER;
+use crate::graph::{channels, export, groups};
+use crate::graph::validate::{self, LinkError};
+use crate::models::graph::{AudioGraph, Flow, GroupBy, HardwareRoute, Link, NodeConnection, NodeGroup, Routes};
 
 pub async fn get_graph(
     State(state): State<AppState>,
-) -> Json<AudioGraph> {
+) -> ([(&'static str, String); 1], Json<Arc<AudioGraph>>) {
     let graph_manager = state.graph.read();
-    Json(graph_manager.get_graph().clone())
+    (
+        [(REVISION_HEADER, graph_manager.revision().to_string())],
+        Json(graph_manager.get_graph()),
+    )
+}
+
+/// The graph as Graphviz DOT, e.g. for `dot -Tpng`.
+pub async fn get_graph_dot(
+    State(state): State<AppState>,
+) -> ([(header::HeaderName, &'static str); 1], String) {
+    let graph = state.graph.read().get_graph();
+    ([(header::CONTENT_TYPE, "text/vnd.graphviz; charset=utf-8")], export::dot(&graph))
+}
+
+/// The graph drawn server-side as SVG.
+pub async fn get_graph_svg(
+    State(state): State<AppState>,
+) -> ([(header::HeaderName, &'static str); 1], String) {
+    let graph = state.graph.read().get_graph();
+    ([(header::CONTENT_TYPE, "image/svg+xml")], export::svg(&graph))
+}
+
+#[derive(Deserialize)]
+pub struct GroupQuery {
+    pub by: GroupBy,
+    /// Comma-separated group keys to collapse; every group when omitted.
+    pub groups: Option<String>,
+}
+
+/// Nodes grouped by client, device or application.
+pub async fn get_groups(
+    State(state): State<AppState>,
+    Query(query): Query<GroupQuery>,
+) -> Json<Vec<NodeGroup>> {
+    Json(groups::groups(&state.graph.read().get_graph(), query.by))
+}
+
+/// The graph with groups collapsed into single aggregate nodes.
+pub async fn get_collapsed_graph(
+    State(state): State<AppState>,
+    Query(query): Query<GroupQuery>,
+) -> Json<AudioGraph> {
+    let only: Option<HashSet<String>> = query
+        .groups
+        .map(|keys| keys.split(',').map(|k| k.trim().to_string()).filter(|k| !k.is_empty()).collect());
+    Json(groups::collapse(&state.graph.read().get_graph(), query.by, only.as_ref()))
+}
+
+fn routes(state: &AppState, id: u32, flow: Flow) -> Result<Json<Routes>, StatusCode> {
+    let graph = state.graph.read();
+    if graph.get_node(id).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(graph.routes(id, flow)))
+}
+
+/// Where a node's audio goes: every node reached and the routes there.
+pub async fn get_downstream(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> Result<Json<Routes>, StatusCode> {
+    routes(&state, id, Flow::Downstream)
+}
+
+/// What feeds a node, following links back to their sources.
+pub async fn get_upstream(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> Result<Json<Routes>, StatusCode> {
+    routes(&state, id, Flow::Upstream)
+}
+
+/// Hardware sinks a node's audio ultimately plays on.
+pub async fn get_hardware_outputs(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> Result<Json<Vec<HardwareRoute>>, StatusCode> {
+    let graph = state.graph.read();
+    if graph.get_node(id).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(graph.hardware_outputs(id)))
 }
 
 #[derive(Deserialize)]
@@ -21,21 +107,55 @@ pub struct CreateLinkRequest {
     pub output_port: u32,
     pub input_node: u32,
     pub input_port: u32,
+    /// Create the link even if it closes a feedback loop.
+    #[serde(default)]
+    pub allow_cycle: bool,
+}
+
+/// Body of a refused link request: the `LinkError` fields plus a readable
+/// `message`.
+#[derive(Serialize)]
+pub struct LinkErrorBody {
+    #[serde(flatten)]
+    pub error: LinkError,
+    pub message: String,
+}
+
+fn refuse(error: LinkError) -> (StatusCode, Json<LinkErrorBody>) {
+    warn!("Refused link: {}", error);
+    let status = match error {
+        LinkError::NodeNotFound { .. } | LinkError::PortNotFound { .. } => StatusCode::NOT_FOUND,
+        LinkError::LinkExists { .. } | LinkError::Cycle { .. } => StatusCode::CONFLICT,
+        _ => StatusCode::BAD_REQUEST,
+    };
+    let message = error.to_string();
+    (status, Json(LinkErrorBody { error, message }))
 }
 
 pub async fn create_link(
     State(state): State<AppState>,
     Json(payload): Json<CreateLinkRequest>,
-) -> StatusCode {
+) -> Result<StatusCode, (StatusCode, Json<LinkErrorBody>)> {
     info!("API Request: Create link from {}:{} to {}:{}", 
         payload.output_node, payload.output_port, payload.input_node, payload.input_port);
+    let checked = validate::check_link(
+        &state.graph.read(),
+        payload.output_node,
+        payload.output_port,
+        payload.input_node,
+        payload.input_port,
+        payload.allow_cycle,
+    );
+    if let Err(error) = checked {
+        return Err(refuse(error));
+    }
     state.pw_handler.create_link(
         payload.output_node,
         payload.output_port,
         payload.input_node,
         payload.input_port
     );
-    StatusCode::ACCEPTED
+    Ok(StatusCode::ACCEPTED)
 }
 
 #[derive(Deserialize)]
@@ -52,3 +172,50 @@ pub async fn delete_link(
     state.pw_handler.delete_link(payload.link_id);
     StatusCode::ACCEPTED
 }
+
+#[derive(Deserialize)]
+pub struct ConnectNodesRequest {
+    pub output_node: u32,
+    pub input_node: u32,
+    #[serde(default)]
+    pub allow_cycle: bool,
+}
+
+/// Links every audio output of one node to the matching inputs of another,
+/// paired by channel.
+pub async fn connect_nodes(
+    State(state): State<AppState>,
+    Json(payload): Json<ConnectNodesRequest>,
+) -> Result<Json<NodeConnection>, (StatusCode, Json<LinkErrorBody>)> {
+    info!("API Request: Connect node {} to {}", payload.output_node, payload.input_node);
+    let connection = channels::plan_connect(
+        &state.graph.read(),
+        payload.output_node,
+        payload.input_node,
+        payload.allow_cycle,
+    )
+    .map_err(refuse)?;
+    for pair in &connection.created {
+        state.pw_handler.create_link(payload.output_node, pair.output_port, payload.input_node, pair.input_port);
+    }
+    Ok(Json(connection))
+}
+
+#[derive(Deserialize)]
+pub struct DisconnectNodesRequest {
+    pub output_node: u32,
+    pub input_node: u32,
+}
+
+/// Removes every link from one node into another and returns them.
+pub async fn disconnect_nodes(
+    State(state): State<AppState>,
+    Json(payload): Json<DisconnectNodesRequest>,
+) -> Json<Vec<Link>> {
+    info!("API Request: Disconnect node {} from {}", payload.output_node, payload.input_node);
+    let links = channels::links_between(&state.graph.read(), payload.output_node, payload.input_node);
+    for link in &links {
+        state.pw_handler.delete_link(link.id);
+    }
+    Json(links)
+}
@@ -0,0 +1,35 @@
+use axum::{extract::State, http::StatusCode, Json};
+use std::collections::BTreeMap;
+use tracing::{error, info};
+use crate::AppState;
+use crate::graph::layout;
+use crate::models::layout::{Layout, Position};
+use crate::utils::broadcast::ServerEvent;
+
+/// Saved node positions, plus layered positions for nodes without one.
+pub async fn get_layout(State(state): State<AppState>) -> Json<Layout> {
+    let saved = state.layout.positions();
+    let auto = layout::auto_positions(&state.graph.read().get_graph(), &saved);
+    Json(Layout { saved, auto })
+}
+
+/// Merges positions into the saved layout; `null` clears a node's position.
+/// Other clients are told about whatever changed.
+pub async fn update_layout(
+    State(state): State<AppState>,
+    Json(changes): Json<BTreeMap<String, Option<Position>>>,
+) -> Result<Json<BTreeMap<String, Option<Position>>>, StatusCode> {
+    let finite = changes.values().flatten().all(|p| p.x.is_finite() && p.y.is_finite());
+    if !finite || changes.keys().any(|name| name.is_empty()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let changed = state.layout.update(changes).map_err(|e| {
+        error!("Failed to save layout: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !changed.is_empty() {
+        info!("API Request: Layout changed for {} nodes", changed.len());
+        state.broadcaster.send(ServerEvent::LayoutChanged(changed.clone()));
+    }
+    Ok(Json(changed))
+}
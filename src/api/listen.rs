@@ -0,0 +1,91 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use futures::{sink::SinkExt, stream::StreamExt};
+use serde::Deserialize;
+use tracing::info;
+use crate::AppState;
+use crate::audio::listen::{self, ListenFormat, ListenTarget};
+use crate::models::device::DeviceType;
+
+#[derive(Deserialize)]
+pub struct ListenParams {
+    #[serde(default = "default_bitrate")]
+    pub bitrate: u32, // kbps
+    #[serde(default = "default_channels")]
+    pub channels: u32,
+}
+
+fn default_bitrate() -> u32 {
+    768
+}
+
+fn default_channels() -> u32 {
+    2
+}
+
+/// Streams a node's audio as PCM over a WebSocket. The first message is a
+/// JSON text frame describing the format; every following message is a
+/// binary frame of interleaved samples.
+pub async fn handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+    Query(params): Query<ListenParams>,
+) -> Response {
+    let target = {
+        let audio = state.audio.read();
+        let Some(device) = audio.get_device(id) else {
+            return StatusCode::NOT_FOUND.into_response();
+        };
+        ListenTarget {
+            node_id: device.id,
+            node_name: device.name.clone(),
+            is_sink: matches!(device.device_type, DeviceType::Sink),
+        }
+    };
+    let Ok(format) = ListenFormat::for_bitrate(params.bitrate, params.channels) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    ws.on_upgrade(move |socket| handle_socket(socket, state, target, format))
+}
+
+async fn handle_socket(socket: WebSocket, state: AppState, target: ListenTarget, format: ListenFormat) {
+    let (mut sender, mut receiver) = socket.split();
+
+    let Ok(header) = serde_json::to_string(&format) else {
+        return;
+    };
+    if sender.send(Message::Text(header)).await.is_err() {
+        return;
+    }
+
+    let node_id = target.node_id;
+    let mut session = listen::start(state.pw_handler.clone(), target, &format);
+    info!("Listen client connected to node {}", node_id);
+
+    loop {
+        tokio::select! {
+            chunk = session.receiver.recv() => {
+                let Some(chunk) = chunk else { break };
+                if sender.send(Message::Binary(chunk)).await.is_err() {
+                    break;
+                }
+            }
+            msg = receiver.next() => {
+                match msg {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    info!("Listen client disconnected from node {}", node_id);
+}
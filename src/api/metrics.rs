@@ -0,0 +1,17 @@
+use axum::{extract::State, Json};
+use serde::Serialize;
+use crate::AppState;
+use crate::audio::coalesce::VolumeMetrics;
+
+#[derive(Serialize)]
+pub struct Metrics {
+    pub volume: VolumeMetrics,
+}
+
+/// Counters since startup. `volume.coalesced` counts fader moves that were
+/// superseded before reaching `wpctl`.
+pub async fn get_metrics(State(state): State<AppState>) -> Json<Metrics> {
+    Json(Metrics {
+        volume: state.pw_handler.volume_stats().metrics(),
+    })
+}
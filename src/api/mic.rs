@@ -0,0 +1,67 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use futures::stream::StreamExt;
+use serde::Deserialize;
+use tracing::{error, info};
+use crate::AppState;
+use crate::audio::mic::{self, MicFormat};
+
+#[derive(Deserialize)]
+pub struct MicParams {
+    pub rate: u32,
+    #[serde(default = "default_channels")]
+    pub channels: u32,
+    #[serde(default = "default_name")]
+    pub name: String,
+}
+
+fn default_channels() -> u32 {
+    1
+}
+
+fn default_name() -> String {
+    "Browser Microphone".to_string()
+}
+
+/// Accepts binary frames of interleaved s16le PCM at `rate` and plays them
+/// into a virtual source node that lives as long as the connection.
+pub async fn handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Query(params): Query<MicParams>,
+) -> Response {
+    let format = MicFormat {
+        sample_rate: params.rate,
+        channels: params.channels,
+    };
+    if let Err(e) = format.validate() {
+        return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+    }
+    ws.on_upgrade(move |socket| handle_socket(socket, state, params, format))
+}
+
+async fn handle_socket(mut socket: WebSocket, state: AppState, params: MicParams, format: MicFormat) {
+    let mut session = match mic::start(state.pw_handler.clone(), &params.name, format) {
+        Ok(session) => session,
+        Err(e) => {
+            error!("Failed to create microphone source: {}", e);
+            return;
+        }
+    };
+    info!("Microphone client connected as source {}", session.id);
+
+    while let Some(msg) = socket.next().await {
+        match msg {
+            Ok(Message::Binary(data)) => session.push(&data),
+            Ok(Message::Close(_)) | Err(_) => break,
+            _ => {}
+        }
+    }
+    // Dropping the session removes the node
+}
@@ -1,3 +1,18 @@
 pub mod devices;
 pub mod websocket;
+pub mod events;
 pub mod graph;
+pub mod layout;
+pub mod recordings;
+pub mod playback;
+pub mod listen;
+pub mod mic;
+pub mod rtp;
+pub mod presets;
+pub mod rules;
+pub mod metrics;
+pub mod rpc;
+
+/// Revision of the state a REST response reflects; pass it as `since` when
+/// opening `/ws` or `/api/events` to receive only later changes.
+pub const REVISION_HEADER: &str = "x-revision";
@@ -0,0 +1,123 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{error, info};
+use crate::AppState;
+use crate::audio::player::PlayTarget;
+use crate::models::device::DeviceType;
+use crate::models::playback::{PlaybackInfo, PlaybackSignal};
+use crate::utils::files::FileEntry;
+
+#[derive(Serialize)]
+pub struct PlaybackResponse {
+    pub active: Vec<PlaybackInfo>,
+    pub sounds: Vec<FileEntry>,
+}
+
+pub async fn list_playback(
+    State(state): State<AppState>,
+) -> Result<Json<PlaybackResponse>, StatusCode> {
+    let sounds = state.player.list_sounds().map_err(|e| {
+        error!("Failed to list sounds: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(PlaybackResponse {
+        active: state.player.list_active(),
+        sounds,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct StartPlaybackRequest {
+    pub node_id: u32,
+    pub signal: PlaybackSignal,
+    #[serde(default = "default_level")]
+    pub level: f32,
+    pub duration_secs: Option<f32>,
+    #[serde(default = "default_channels")]
+    pub channels: u32,
+}
+
+fn default_level() -> f32 {
+    0.25
+}
+
+fn default_channels() -> u32 {
+    2
+}
+
+pub async fn start_playback(
+    State(state): State<AppState>,
+    Json(payload): Json<StartPlaybackRequest>,
+) -> Result<Json<PlaybackInfo>, StatusCode> {
+    info!("API Request: Start playback {:?} on node {}", payload.signal, payload.node_id);
+    let target = {
+        let audio = state.audio.read();
+        let device = audio.get_device(payload.node_id).ok_or(StatusCode::NOT_FOUND)?;
+        if !matches!(device.device_type, DeviceType::Sink) {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        PlayTarget {
+            node_id: device.id,
+            node_name: device.name.clone(),
+            channels: payload.channels,
+        }
+    };
+    let duration = payload
+        .duration_secs
+        .filter(|d| d.is_finite() && *d > 0.0)
+        .map(Duration::from_secs_f32);
+
+    state
+        .player
+        .start(target, payload.signal, payload.level, duration)
+        .map(Json)
+        .map_err(|e| {
+            error!("Failed to start playback: {}", e);
+            StatusCode::BAD_REQUEST
+        })
+}
+
+pub async fn stop_playback(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> StatusCode {
+    info!("API Request: Stop playback {}", id);
+    if state.player.stop(id) {
+        StatusCode::ACCEPTED
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+pub async fn upload_sound(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    body: Bytes,
+) -> Result<Json<FileEntry>, StatusCode> {
+    info!("API Request: Upload sound {} ({} bytes)", name, body.len());
+    state.player.save_sound(&name, &body).map(Json).map_err(|e| {
+        error!("Failed to save sound {}: {}", name, e);
+        StatusCode::BAD_REQUEST
+    })
+}
+
+pub async fn delete_sound(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> StatusCode {
+    info!("API Request: Delete sound {}", name);
+    match state.player.delete_sound(&name) {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            error!("Failed to delete sound {}: {}", name, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
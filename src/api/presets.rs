@@ -0,0 +1,208 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use tracing::{error, info, warn};
+use crate::AppState;
+use crate::graph::{presets, qpwgraph};
+use crate::models::preset::{Preset, PresetApply};
+use crate::utils::files;
+
+pub async fn list_presets(State(state): State<AppState>) -> Json<Vec<Preset>> {
+    Json(state.presets.list())
+}
+
+#[derive(Deserialize)]
+pub struct PresetNameRequest {
+    pub name: String,
+}
+
+fn valid_name(name: &str) -> Result<&str, StatusCode> {
+    let name = name.trim();
+    if name.is_empty() || name.len() > 128 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(name)
+}
+
+/// Saves the current links under `name`, replacing an existing preset.
+pub async fn save_preset(
+    State(state): State<AppState>,
+    Json(payload): Json<PresetNameRequest>,
+) -> Result<Json<Preset>, StatusCode> {
+    let name = valid_name(&payload.name)?;
+    let preset = presets::capture(name, &state.graph.read());
+    info!("API Request: Save preset {} ({} links)", name, preset.links.len());
+    state.presets.save(preset.clone()).map_err(|e| {
+        error!("Failed to save preset {}: {}", name, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(preset))
+}
+
+pub async fn rename_preset(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(payload): Json<PresetNameRequest>,
+) -> StatusCode {
+    let Ok(to) = valid_name(&payload.name) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    info!("API Request: Rename preset {} to {}", name, to);
+    if to != name && state.presets.get(to).is_some() {
+        return StatusCode::CONFLICT;
+    }
+    match state.presets.rename(&name, to) {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            error!("Failed to rename preset {}: {}", name, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+pub async fn delete_preset(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> StatusCode {
+    info!("API Request: Delete preset {}", name);
+    match state.presets.delete(&name) {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            error!("Failed to delete preset {}: {}", name, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ApplyParams {
+    /// Also delete every link the preset doesn't describe.
+    #[serde(default)]
+    pub exclusive: bool,
+}
+
+/// Creates the preset's links that are missing from the graph. Other links
+/// are left alone unless `exclusive` is set.
+pub async fn apply_preset(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(params): Query<ApplyParams>,
+) -> Result<Json<PresetApply>, StatusCode> {
+    let preset = state.presets.get(&name).ok_or(StatusCode::NOT_FOUND)?;
+    let resolved = presets::resolve(&state.graph.read(), &preset, params.exclusive);
+    info!("API Request: Apply preset {} ({} to create, {} to remove, {} present, {} unavailable)",
+        name, resolved.create.len(), resolved.apply.removed.len(),
+        resolved.apply.existing.len(), resolved.apply.missing.len());
+    resolved.execute(&state.pw_handler);
+    Ok(Json(resolved.apply))
+}
+
+/// Reports what `apply_preset` would do with the same parameters, without
+/// touching the graph.
+pub async fn dry_run_preset(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(params): Query<ApplyParams>,
+) -> Result<Json<PresetApply>, StatusCode> {
+    let preset = state.presets.get(&name).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(presets::resolve(&state.graph.read(), &preset, params.exclusive).apply))
+}
+
+/// Enforces the preset from now on, replacing any other enforced preset,
+/// and applies it exclusively right away. Survives restarts.
+pub async fn enforce_preset(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<PresetApply>, StatusCode> {
+    info!("API Request: Enforce preset {}", name);
+    match state.presets.set_enforced(Some(&name)) {
+        Ok(true) => {}
+        Ok(false) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to enforce preset {}: {}", name, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+    let preset = state.presets.get(&name).ok_or(StatusCode::NOT_FOUND)?;
+    let resolved = presets::resolve(&state.graph.read(), &preset, true);
+    resolved.execute(&state.pw_handler);
+    Ok(Json(resolved.apply))
+}
+
+pub async fn release_preset(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> StatusCode {
+    info!("API Request: Stop enforcing preset {}", name);
+    match state.presets.get(&name) {
+        None => return StatusCode::NOT_FOUND,
+        Some(preset) if !preset.enforced => return StatusCode::OK,
+        Some(_) => {}
+    }
+    match state.presets.set_enforced(None) {
+        Ok(_) => StatusCode::OK,
+        Err(e) => {
+            error!("Failed to stop enforcing preset {}: {}", name, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ImportParams {
+    /// Preset name; defaults to the patchbay's own name.
+    pub name: Option<String>,
+}
+
+/// Stores a qpwgraph patchbay file (the request body) as a preset,
+/// replacing one with the same name. Non-audio items are dropped.
+pub async fn import_preset(
+    State(state): State<AppState>,
+    Query(params): Query<ImportParams>,
+    body: String,
+) -> Result<Json<Preset>, StatusCode> {
+    let patchbay = qpwgraph::parse(&body).map_err(|e| {
+        warn!("Rejected qpwgraph patchbay: {:#}", e);
+        StatusCode::BAD_REQUEST
+    })?;
+    let name = params.name.or(patchbay.name).ok_or(StatusCode::BAD_REQUEST)?;
+    let name = valid_name(&name)?;
+    info!("API Request: Import preset {} ({} links, {} non-audio items skipped)",
+        name, patchbay.links.len(), patchbay.skipped);
+    let preset = Preset {
+        name: name.to_string(),
+        links: patchbay.links,
+        saved: files::unix_now(),
+        enforced: false,
+    };
+    state.presets.save(preset.clone()).map_err(|e| {
+        error!("Failed to save preset {}: {}", name, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(state.presets.get(name).unwrap_or(preset)))
+}
+
+/// Downloads a preset as a qpwgraph patchbay file.
+pub async fn export_preset(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Response {
+    let Some(preset) = state.presets.get(&name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/xml")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}.qpwgraph\"", files::sanitize(&preset.name)),
+        )
+        .body(Body::from(qpwgraph::write(&preset)))
+        .unwrap()
+}
@@ -0,0 +1,124 @@
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use crate::AppState;
+use crate::audio::recorder::RecordTarget;
+use crate::models::device::DeviceType;
+use crate::models::recording::{RecordingFormat, RecordingInfo};
+use crate::utils::files::FileEntry;
+
+#[derive(Serialize)]
+pub struct RecordingsResponse {
+    pub active: Vec<RecordingInfo>,
+    pub files: Vec<FileEntry>,
+}
+
+pub async fn list_recordings(
+    State(state): State<AppState>,
+) -> Result<Json<RecordingsResponse>, StatusCode> {
+    let files = state.recorder.list_files().map_err(|e| {
+        error!("Failed to list recordings: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(RecordingsResponse {
+        active: state.recorder.list_active(),
+        files,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct StartRecordingRequest {
+    pub node_id: u32,
+    #[serde(default)]
+    pub format: RecordingFormat,
+    #[serde(default = "default_channels")]
+    pub channels: u32,
+}
+
+fn default_channels() -> u32 {
+    2
+}
+
+pub async fn start_recording(
+    State(state): State<AppState>,
+    Json(payload): Json<StartRecordingRequest>,
+) -> Result<Json<RecordingInfo>, StatusCode> {
+    info!("API Request: Start recording node {}", payload.node_id);
+    let target = {
+        let audio = state.audio.read();
+        let device = audio.get_device(payload.node_id).ok_or(StatusCode::NOT_FOUND)?;
+        RecordTarget {
+            node_id: device.id,
+            node_name: device.name.clone(),
+            is_sink: matches!(device.device_type, DeviceType::Sink),
+        }
+    };
+
+    state
+        .recorder
+        .start(target, payload.format, payload.channels)
+        .map(Json)
+        .map_err(|e| {
+            error!("Failed to start recording: {}", e);
+            StatusCode::BAD_REQUEST
+        })
+}
+
+pub async fn stop_recording(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> StatusCode {
+    info!("API Request: Stop recording {}", id);
+    if state.recorder.stop(id) {
+        StatusCode::ACCEPTED
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+pub async fn download_recording(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Response {
+    let Some(path) = state.recorder.file_path(&name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match tokio::fs::read(&path).await {
+        Ok(data) => {
+            let mime = mime_guess::from_path(&path).first_or_octet_stream();
+            Response::builder()
+                .header(header::CONTENT_TYPE, mime.as_ref())
+                .header(
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}\"", name),
+                )
+                .body(Body::from(data))
+                .unwrap()
+        }
+        Err(e) => {
+            error!("Failed to read recording {}: {}", name, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+pub async fn delete_recording(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> StatusCode {
+    info!("API Request: Delete recording {}", name);
+    match state.recorder.delete_file(&name) {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            error!("Failed to delete recording {}: {}", name, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
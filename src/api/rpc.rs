@@ -0,0 +1,325 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tracing::{info, Level};
+use crate::AppState;
+use crate::audio::meter::{MeterLease, MeterTarget};
+use crate::graph::channels;
+use crate::graph::validate::{self, LinkError};
+use crate::api::devices::VolumeState;
+use crate::models::device::{DeviceType, VolumeChange, VolumeScale};
+use crate::utils::topics::{Topic, TopicFilter};
+
+/// A command sent by a client over `/ws`:
+/// `{"id": 1, "method": "set_volume", "params": {"id": 42, "volume": 0.5}}`.
+#[derive(Deserialize)]
+pub struct RpcRequest {
+    #[serde(default)]
+    pub id: Value,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+/// Reply to an `RpcRequest`, carrying the same `id` and either `result` or
+/// `error`. Tagged `"type": "Response"` so clients can tell it from events.
+#[derive(Serialize)]
+#[serde(tag = "type", rename = "Response")]
+pub struct RpcResponse {
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RpcError {
+    pub code: &'static str,
+    pub message: String,
+    /// Structured details for errors clients may want to act on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn parse(message: impl Into<String>) -> Self {
+        Self::new("parse_error", message)
+    }
+
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new("invalid_params", message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new("not_found", message)
+    }
+}
+
+impl From<LinkError> for RpcError {
+    fn from(e: LinkError) -> Self {
+        Self {
+            code: e.code(),
+            message: e.to_string(),
+            data: serde_json::to_value(&e).ok(),
+        }
+    }
+}
+
+impl RpcResponse {
+    pub fn ok(id: Value, result: Value) -> Self {
+        Self {
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub fn err(id: Value, error: RpcError) -> Self {
+        Self {
+            id,
+            result: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// Per-connection state that commands can change.
+pub struct Session {
+    pub filter: TopicFilter,
+    meters: HashMap<u32, MeterLease>,
+    /// Set when the client subscribes to state again and needs a fresh snapshot.
+    pub resync: bool,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            filter: TopicFilter::default(),
+            meters: HashMap::new(),
+            resync: false,
+        }
+    }
+
+    /// The current subscription, as returned by `subscribe`/`unsubscribe`.
+    fn subscription(&self) -> Value {
+        json!({
+            "topics": self.filter.topics,
+            "log_level": self.filter.log_level.to_string().to_lowercase(),
+            "meters": self.filter.meters,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct SetVolumeParams {
+    id: u32,
+    volume: VolumeChange,
+    #[serde(default)]
+    scale: VolumeScale,
+    timestamp: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct SetMuteParams {
+    id: u32,
+    /// Omitted to toggle.
+    muted: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct CreateLinkParams {
+    output_node: u32,
+    output_port: u32,
+    input_node: u32,
+    input_port: u32,
+    #[serde(default)]
+    allow_cycle: bool,
+}
+
+#[derive(Deserialize)]
+struct NodesParams {
+    output_node: u32,
+    input_node: u32,
+    #[serde(default)]
+    allow_cycle: bool,
+}
+
+#[derive(Deserialize)]
+struct DeleteLinkParams {
+    link_id: u32,
+}
+
+/// Topics, log level and metered nodes to add. All omitted means every
+/// topic, as a plain `subscribe` did before topics existed.
+#[derive(Deserialize, Default)]
+pub struct SubscribeParams {
+    pub topics: Option<Vec<Topic>>,
+    pub log_level: Option<String>,
+    pub meters: Option<Vec<u32>>,
+}
+
+/// Topics and metered nodes to drop. All omitted means everything.
+#[derive(Deserialize)]
+struct UnsubscribeParams {
+    topics: Option<Vec<Topic>>,
+    meters: Option<Vec<u32>>,
+}
+
+fn params<T: DeserializeOwned>(value: Value) -> Result<T, RpcError> {
+    // Methods without parameters accept an omitted `params`
+    let value = if value.is_null() { Value::Object(Default::default()) } else { value };
+    serde_json::from_value(value).map_err(|e| RpcError::invalid_params(e.to_string()))
+}
+
+pub fn handle(state: &AppState, session: &mut Session, text: &str) -> RpcResponse {
+    let request: RpcRequest = match serde_json::from_str(text) {
+        Ok(request) => request,
+        Err(e) => return RpcResponse::err(Value::Null, RpcError::parse(e.to_string())),
+    };
+    let id = request.id.clone();
+    match dispatch(state, session, request) {
+        Ok(result) => RpcResponse::ok(id, result),
+        Err(error) => RpcResponse::err(id, error),
+    }
+}
+
+fn dispatch(state: &AppState, session: &mut Session, request: RpcRequest) -> Result<Value, RpcError> {
+    match request.method.as_str() {
+        "set_volume" => {
+            let p: SetVolumeParams = params(request.params)?;
+            let (volume, max) = {
+                let mut audio = state.audio.write();
+                let not_found = || RpcError::not_found(format!("device {}", p.id));
+                let volume = audio.request_volume(p.id, p.volume, p.scale).ok_or_else(not_found)?;
+                (volume, audio.max_volume(p.id).ok_or_else(not_found)?)
+            };
+            state.pw_handler.set_volume(p.id, volume, p.timestamp);
+            Ok(json!(VolumeState::new(volume, max, p.scale)))
+        }
+        "set_mute" => {
+            let p: SetMuteParams = params(request.params)?;
+            let current = state.audio.read().get_device(p.id).map(|d| d.muted)
+                .ok_or_else(|| RpcError::not_found(format!("device {}", p.id)))?;
+            let muted = p.muted.unwrap_or(!current);
+            info!("WS Request: Set mute for device {} to {}", p.id, muted);
+            state.pw_handler.set_mute(p.id, muted);
+            Ok(json!({ "muted": muted }))
+        }
+        "create_link" => {
+            let p: CreateLinkParams = params(request.params)?;
+            info!("WS Request: Create link from {}:{} to {}:{}",
+                p.output_node, p.output_port, p.input_node, p.input_port);
+            validate::check_link(&state.graph.read(), p.output_node, p.output_port,
+                p.input_node, p.input_port, p.allow_cycle)?;
+            state.pw_handler.create_link(p.output_node, p.output_port, p.input_node, p.input_port);
+            Ok(Value::Null)
+        }
+        "delete_link" => {
+            let p: DeleteLinkParams = params(request.params)?;
+            if state.graph.read().get_link(p.link_id).is_none() {
+                return Err(RpcError::not_found(format!("link {}", p.link_id)));
+            }
+            info!("WS Request: Delete link {}", p.link_id);
+            state.pw_handler.delete_link(p.link_id);
+            Ok(Value::Null)
+        }
+        "connect_nodes" => {
+            let p: NodesParams = params(request.params)?;
+            info!("WS Request: Connect node {} to {}", p.output_node, p.input_node);
+            let connection = channels::plan_connect(&state.graph.read(), p.output_node, p.input_node, p.allow_cycle)?;
+            for pair in &connection.created {
+                state.pw_handler.create_link(p.output_node, pair.output_port, p.input_node, pair.input_port);
+            }
+            Ok(json!(connection))
+        }
+        "disconnect_nodes" => {
+            let p: NodesParams = params(request.params)?;
+            info!("WS Request: Disconnect node {} from {}", p.output_node, p.input_node);
+            let links = channels::links_between(&state.graph.read(), p.output_node, p.input_node);
+            for link in &links {
+                state.pw_handler.delete_link(link.id);
+            }
+            Ok(json!(links))
+        }
+        "subscribe" => {
+            let p: SubscribeParams = params(request.params)?;
+            subscribe(state, session, p)
+        }
+        "unsubscribe" => {
+            let p: UnsubscribeParams = params(request.params)?;
+            if p.topics.is_none() && p.meters.is_none() {
+                session.filter = TopicFilter::none();
+                session.meters.clear();
+            }
+            for topic in p.topics.unwrap_or_default() {
+                session.filter.topics.remove(&topic);
+            }
+            for id in p.meters.unwrap_or_default() {
+                session.filter.meters.remove(&id);
+                session.meters.remove(&id);
+            }
+            Ok(session.subscription())
+        }
+        other => Err(RpcError::new("method_not_found", format!("unknown method {}", other))),
+    }
+}
+
+/// Adds to the session's subscription. Nothing changes if any parameter is
+/// invalid. Turning state topics back on requests a resync snapshot.
+pub fn subscribe(state: &AppState, session: &mut Session, p: SubscribeParams) -> Result<Value, RpcError> {
+    let everything = p.topics.is_none() && p.log_level.is_none() && p.meters.is_none();
+    let log_level = match p.log_level {
+        Some(level) => Some(level.parse::<Level>()
+            .map_err(|_| RpcError::invalid_params(format!("unknown log level {}", level)))?),
+        None => None,
+    };
+    let mut targets = Vec::new();
+    for id in p.meters.unwrap_or_default() {
+        if !session.meters.contains_key(&id) {
+            targets.push(meter_target(state, id)?);
+        }
+    }
+
+    let had_state = session.filter.has_state();
+    let topics = if everything { Topic::ALL.to_vec() } else { p.topics.unwrap_or_default() };
+    session.filter.topics.extend(topics);
+    if let Some(level) = log_level {
+        session.filter.log_level = level;
+    }
+    for target in targets {
+        let id = target.node_id;
+        session.meters.insert(id, state.meters.acquire(target));
+        session.filter.meters.insert(id);
+    }
+    if !had_state && session.filter.has_state() {
+        session.resync = true;
+    }
+    Ok(session.subscription())
+}
+
+fn meter_target(state: &AppState, id: u32) -> Result<MeterTarget, RpcError> {
+    let audio = state.audio.read();
+    let Some(device) = audio.get_device(id) else {
+        return Err(RpcError::not_found(format!("device {}", id)));
+    };
+    Ok(MeterTarget {
+        node_id: device.id,
+        node_name: device.name.clone(),
+        is_sink: matches!(device.device_type, DeviceType::Sink),
+        // Layouts the capture stream can position; anything else is metered as stereo
+        channels: match device.channels.len() {
+            n @ (1 | 2 | 6 | 8) => n as u32,
+            _ => 2,
+        },
+    })
+}
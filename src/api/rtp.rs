@@ -0,0 +1,50 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use tracing::{error, info};
+use crate::AppState;
+use crate::models::rtp::{RtpConfig, RtpInstance};
+
+pub async fn list_rtp(State(state): State<AppState>) -> Json<Vec<RtpInstance>> {
+    let mut instances = state.rtp.list();
+    // Join the node each module created, once it has shown up in the registry
+    let graph = state.graph.read();
+    for instance in instances.iter_mut() {
+        if let Some(node) = graph.nodes().find(|n| n.name == instance.node_name) {
+            instance.node_id = Some(node.id);
+            instance.properties = node.properties.clone();
+        }
+    }
+    Json(instances)
+}
+
+pub async fn load_rtp(
+    State(state): State<AppState>,
+    Json(payload): Json<RtpConfig>,
+) -> Result<Json<RtpInstance>, StatusCode> {
+    info!("API Request: Load RTP {:?} {}:{}", payload.direction, payload.address, payload.port);
+    let rtp = state.rtp.clone();
+    tokio::task::spawn_blocking(move || rtp.load(payload))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map(Json)
+        .map_err(|e| {
+            error!("Failed to load RTP module: {}", e);
+            StatusCode::BAD_REQUEST
+        })
+}
+
+pub async fn unload_rtp(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> StatusCode {
+    info!("API Request: Unload RTP {}", id);
+    let rtp = state.rtp.clone();
+    match tokio::task::spawn_blocking(move || rtp.unload(id)).await {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -0,0 +1,69 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use tracing::{error, info, warn};
+use crate::AppState;
+use crate::graph::rules::RuleEngine;
+use crate::models::rule::{Rule, RuleFiring, RuleSpec};
+
+pub async fn list_rules(State(state): State<AppState>) -> Json<Vec<Rule>> {
+    Json(state.rules.list())
+}
+
+/// Recently fired rules, newest first.
+pub async fn rule_log(State(state): State<AppState>) -> Json<Vec<RuleFiring>> {
+    Json(state.rules.log())
+}
+
+fn check(spec: &RuleSpec) -> Result<(), StatusCode> {
+    RuleEngine::check(spec).map_err(|e| {
+        warn!("Rejected rule {}: {:#}", spec.name, e);
+        StatusCode::BAD_REQUEST
+    })
+}
+
+pub async fn create_rule(
+    State(state): State<AppState>,
+    Json(payload): Json<RuleSpec>,
+) -> Result<Json<Rule>, StatusCode> {
+    info!("API Request: Create rule {}", payload.name);
+    check(&payload)?;
+    state.rules.create(payload).map(Json).map_err(|e| {
+        error!("Failed to save rule: {:#}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+pub async fn update_rule(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+    Json(payload): Json<RuleSpec>,
+) -> Result<Json<Rule>, StatusCode> {
+    info!("API Request: Update rule {}", id);
+    check(&payload)?;
+    match state.rules.update(id, payload) {
+        Ok(Some(rule)) => Ok(Json(rule)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to save rule {}: {:#}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn delete_rule(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+) -> StatusCode {
+    info!("API Request: Delete rule {}", id);
+    match state.rules.delete(id) {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            error!("Failed to delete rule {}: {:#}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
@@ -1,42 +1,193 @@
 use axum::{
     extract::{
         ws::{WebSocket, WebSocketUpgrade, Message},
-        State,
+        Query, State,
     },
-    response::Response,
+    http::StatusCode,
+    response::{IntoResponse, Response},
 };
 use crate::AppState;
+use crate::api::rpc::{self, Session, SubscribeParams};
+use crate::audio::controller::AudioController;
+use crate::graph::manager::GraphManager;
+use crate::utils::broadcast::{Catchup, ServerEvent, ServerInfo, Stamped, StateSnapshot};
+use crate::utils::topics::Topic;
 use futures::{sink::SinkExt, stream::StreamExt};
-use tracing::info;
+use serde::Deserialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{info, warn};
+
+#[derive(Deserialize)]
+pub struct WsParams {
+    /// Last revision the client has applied, when reconnecting.
+    pub since: Option<u64>,
+    /// Comma-separated topics; all of them when omitted.
+    pub topics: Option<String>,
+    /// Most verbose log level to receive, e.g. `warn`.
+    pub log_level: Option<String>,
+    /// Comma-separated node ids to receive peak meters for.
+    pub meters: Option<String>,
+}
 
 pub async fn handler(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
+    Query(params): Query<WsParams>,
 ) -> Response {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+    let session = match open_session(&state, &params.topics, &params.log_level, &params.meters) {
+        Ok(session) => session,
+        Err(status) => return status.into_response(),
+    };
+    ws.on_upgrade(move |socket| handle_socket(socket, state, params.since, session))
 }
 
-async fn handle_socket(socket: WebSocket, state: AppState) {
-    let (mut sender, mut _receiver) = socket.split();
-    
-    // Subscribe to broadcast events
-    let mut rx = state.broadcaster.subscribe();
-    info!("WebSocket client subscribed to logs");
+/// Builds a session from `topics`, `log_level` and `meters` query
+/// parameters, shared by `/ws` and `/api/events`. Omitted `topics` means
+/// all of them.
+pub fn open_session(
+    state: &AppState,
+    topics: &Option<String>,
+    log_level: &Option<String>,
+    meters: &Option<String>,
+) -> Result<Session, StatusCode> {
+    fn list<T: std::str::FromStr>(s: &str) -> Result<Vec<T>, StatusCode> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse().map_err(|_| StatusCode::BAD_REQUEST))
+            .collect()
+    }
+
+    let mut session = Session::new();
+    if topics.is_none() && log_level.is_none() && meters.is_none() {
+        return Ok(session);
+    }
+    let subscribe = SubscribeParams {
+        topics: topics.as_deref().map(list::<Topic>).transpose()?,
+        log_level: log_level.clone(),
+        meters: meters.as_deref().map(list::<u32>).transpose()?,
+    };
+    if subscribe.topics.is_some() {
+        session.filter.topics.clear();
+    }
+    if let Err(e) = rpc::subscribe(state, &mut session, subscribe) {
+        warn!("Rejected event subscription: {}", e.message);
+        return Err(match e.code {
+            "not_found" => StatusCode::NOT_FOUND,
+            _ => StatusCode::BAD_REQUEST,
+        });
+    }
+    // Every connection starts with a snapshot or catch-up anyway
+    session.resync = false;
+    Ok(session)
+}
+
+fn snapshot_at(audio: &AudioController, graph: &GraphManager, revision: u64) -> Stamped {
+    Stamped {
+        revision,
+        event: ServerEvent::Snapshot(StateSnapshot {
+            server: ServerInfo::current(),
+            devices: audio.list_devices(),
+            graph: graph.get_graph(),
+            defaults: audio.defaults().clone(),
+        }),
+    }
+}
+
+/// Full device and graph state as of the broadcaster's current revision.
+/// The read locks keep the event loop from applying anything in between.
+pub fn snapshot(state: &AppState) -> Stamped {
+    let audio = state.audio.read();
+    let graph = state.graph.read();
+    snapshot_at(&audio, &graph, state.broadcaster.revision())
+}
 
+/// Subscribes and returns the messages that bring the client up to date:
+/// a snapshot for a fresh client, or what it missed since `since`. Holding
+/// the read locks across the subscription means no event falls in between.
+pub fn subscribe(state: &AppState, since: Option<u64>) -> (broadcast::Receiver<Stamped>, Vec<Stamped>) {
+    let audio = state.audio.read();
+    let graph = state.graph.read();
+    let Some(since) = since else {
+        let (rx, revision) = state.broadcaster.subscribe_at();
+        return (rx, vec![snapshot_at(&audio, &graph, revision)]);
+    };
+
+    let (rx, catchup) = state.broadcaster.subscribe_since(since);
+    let messages = match catchup {
+        Catchup::Deltas(events) => events,
+        Catchup::Snapshot(revision) => vec![snapshot_at(&audio, &graph, revision)],
+    };
+    (rx, messages)
+}
+
+async fn handle_socket(socket: WebSocket, state: AppState, since: Option<u64>, mut session: Session) {
+    let (mut sender, mut receiver) = socket.split();
+
+    let (mut rx, catchup) = subscribe(&state, since);
+    let mut meter_rx = state.broadcaster.subscribe_meters();
+    info!("WebSocket client subscribed (since {:?}, {} catch-up messages, topics {:?})",
+        since, catchup.len(), session.filter.topics);
+
+    for stamped in catchup {
+        if !session.filter.matches(&stamped.event) {
+            continue;
+        }
+        let Ok(msg) = serde_json::to_string(&stamped) else {
+            continue;
+        };
+        if sender.send(Message::Text(msg)).await.is_err() {
+            return;
+        }
+    }
+
+    // Events at or below this were already covered by a resync snapshot
+    let mut skip_through = 0;
     loop {
-        match rx.recv().await {
-            Ok(event) => {
-                if let Ok(msg) = serde_json::to_string(&event) {
-                    if sender.send(Message::Text(msg)).await.is_err() {
-                        break;
+        let msg = tokio::select! {
+            event = rx.recv() => match event {
+                // Filtered before serializing, so unwanted traffic costs nothing
+                Ok(stamped) if !session.filter.matches(&stamped.event) => continue,
+                Ok(stamped) if stamped.revision <= skip_through => continue,
+                Ok(stamped) => serde_json::to_string(&stamped),
+                Err(RecvError::Lagged(n)) => {
+                    if !session.filter.has_state() {
+                        continue;
                     }
+                    warn!("WS client lagged by {} messages, resending state", n);
+                    let snapshot = snapshot(&state);
+                    skip_through = snapshot.revision;
+                    serde_json::to_string(&snapshot)
                 }
+                Err(_) => break,
+            },
+            frame = meter_rx.recv() => match frame {
+                Ok(event) if session.filter.matches(&event) => serde_json::to_string(&event),
+                Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+            incoming = receiver.next() => match incoming {
+                Some(Ok(Message::Text(text))) => {
+                    let response = rpc::handle(&state, &mut session, &text);
+                    if std::mem::take(&mut session.resync) {
+                        let snapshot = snapshot(&state);
+                        skip_through = snapshot.revision;
+                        if let Ok(msg) = serde_json::to_string(&snapshot) {
+                            if sender.send(Message::Text(msg)).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    serde_json::to_string(&response)
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+        };
+        if let Ok(msg) = msg {
+            if sender.send(Message::Text(msg)).await.is_err() {
+                break;
             }
-            Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
-                eprintln!("WS client lagged by {} messages", n);
-                continue;
-            }
-            Err(_) => break,
         }
     }
 }
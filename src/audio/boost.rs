@@ -0,0 +1,49 @@
+use crate::utils::files;
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+/// Names of the nodes allowed above 100% volume, kept in a JSON file so the
+/// opt-in survives restarts and node id changes.
+pub struct BoostStore {
+    path: PathBuf,
+    names: Mutex<BTreeSet<String>>,
+}
+
+impl BoostStore {
+    /// Loads the list from `path`; a missing file means no boosted nodes.
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let names = files::read_json(&path)?;
+        Ok(Self {
+            path,
+            names: Mutex::new(names),
+        })
+    }
+
+    pub fn enabled(&self, name: &str) -> bool {
+        self.names.lock().contains(name)
+    }
+
+    /// Enables or disables boost for node `name`; returns whether it changed.
+    pub fn set(&self, name: &str, enabled: bool) -> Result<bool> {
+        let mut names = self.names.lock();
+        let changed = if enabled {
+            names.insert(name.to_string())
+        } else {
+            names.remove(name)
+        };
+        if !changed {
+            return Ok(false);
+        }
+        if let Err(e) = files::write_json(&self.path, &*names) {
+            if enabled {
+                names.remove(name);
+            } else {
+                names.insert(name.to_string());
+            }
+            return Err(e);
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,209 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Shortest time between two volume changes applied to the same node.
+pub const VOLUME_INTERVAL: Duration = Duration::from_millis(100);
+
+// Client timestamps are only compared within this window; clocks on
+// different clients need not agree, but one drag's commands come from one.
+const STALE_WINDOW: Duration = Duration::from_secs(2);
+
+struct Pending {
+    volume: f32,
+    timestamp: Option<u64>,
+}
+
+struct Applied {
+    at: Instant,
+    timestamp: Option<u64>,
+}
+
+/// Counters for volume commands, shared between the handler and the
+/// PipeWire loop.
+#[derive(Default)]
+pub struct VolumeStats {
+    received: AtomicU64,
+    applied: AtomicU64,
+    coalesced: AtomicU64,
+    stale: AtomicU64,
+    failed: AtomicU64,
+    pending: AtomicU64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VolumeMetrics {
+    /// Commands queued by clients and rules.
+    pub received: u64,
+    /// `wpctl set-volume` runs.
+    pub applied: u64,
+    /// Commands replaced by a later one for the same node before running.
+    pub coalesced: u64,
+    /// Commands dropped for carrying an older timestamp than one already seen.
+    pub stale: u64,
+    pub failed: u64,
+    /// Nodes with a volume waiting for their next slot.
+    pub pending: u64,
+}
+
+impl VolumeStats {
+    pub fn metrics(&self) -> VolumeMetrics {
+        VolumeMetrics {
+            received: self.received.load(Ordering::Relaxed),
+            applied: self.applied.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+            stale: self.stale.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            pending: self.pending.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn record_failure(&self) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Volume commands waiting to be applied, at most one per node: a newer
+/// command replaces the pending one, and a node is set no more often than
+/// `VOLUME_INTERVAL`. A fader drag thus costs a few `wpctl` runs instead of
+/// one per pixel.
+pub struct VolumeQueue {
+    pending: HashMap<u32, Pending>,
+    applied: HashMap<u32, Applied>,
+    stats: Arc<VolumeStats>,
+}
+
+impl VolumeQueue {
+    pub fn new(stats: Arc<VolumeStats>) -> Self {
+        Self {
+            pending: HashMap::new(),
+            applied: HashMap::new(),
+            stats,
+        }
+    }
+
+    /// Queues a volume for node `id`. A command whose timestamp is older
+    /// than the pending or recently applied one is dropped.
+    pub fn push(&mut self, id: u32, volume: f32, timestamp: Option<u64>, now: Instant) {
+        self.stats.received.fetch_add(1, Ordering::Relaxed);
+        let newest = self
+            .pending
+            .get(&id)
+            .and_then(|p| p.timestamp)
+            .or_else(|| {
+                self.applied
+                    .get(&id)
+                    .filter(|a| now.duration_since(a.at) < STALE_WINDOW)
+                    .and_then(|a| a.timestamp)
+            });
+        if let (Some(newest), Some(timestamp)) = (newest, timestamp) {
+            if timestamp < newest {
+                self.stats.stale.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+        if self.pending.insert(id, Pending { volume, timestamp }).is_some() {
+            self.stats.coalesced.fetch_add(1, Ordering::Relaxed);
+        }
+        self.update_gauge();
+    }
+
+    /// Takes the volumes whose node may be set again by `now`.
+    pub fn due(&mut self, now: Instant) -> Vec<(u32, f32, Option<u64>)> {
+        self.applied.retain(|_, a| now.duration_since(a.at) < STALE_WINDOW);
+        let ready: Vec<u32> = self
+            .pending
+            .keys()
+            .copied()
+            .filter(|id| {
+                self.applied
+                    .get(id)
+                    .is_none_or(|a| now.duration_since(a.at) >= VOLUME_INTERVAL)
+            })
+            .collect();
+        let mut due = Vec::with_capacity(ready.len());
+        for id in ready {
+            if let Some(p) = self.pending.remove(&id) {
+                self.applied.insert(id, Applied { at: now, timestamp: p.timestamp });
+                self.stats.applied.fetch_add(1, Ordering::Relaxed);
+                due.push((id, p.volume, p.timestamp));
+            }
+        }
+        self.update_gauge();
+        due
+    }
+
+    fn update_gauge(&self) {
+        self.stats.pending.store(self.pending.len() as u64, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue() -> VolumeQueue {
+        VolumeQueue::new(Arc::new(VolumeStats::default()))
+    }
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn throttles_each_node_to_the_interval() {
+        let mut queue = queue();
+        let t0 = Instant::now();
+        queue.push(1, 0.5, None, t0);
+        assert_eq!(queue.due(t0), [(1, 0.5, None)]);
+
+        queue.push(1, 0.6, None, t0 + ms(10));
+        queue.push(2, 0.7, None, t0 + ms(10));
+        // Node 2 hasn't been set yet; node 1 waits out its interval
+        assert_eq!(queue.due(t0 + ms(10)), [(2, 0.7, None)]);
+        assert!(queue.due(t0 + VOLUME_INTERVAL - ms(1)).is_empty());
+        assert_eq!(queue.due(t0 + VOLUME_INTERVAL), [(1, 0.6, None)]);
+
+        let metrics = queue.stats.metrics();
+        assert_eq!((metrics.received, metrics.applied, metrics.pending), (3, 3, 0));
+    }
+
+    #[test]
+    fn last_value_wins() {
+        let mut queue = queue();
+        let t0 = Instant::now();
+        queue.push(1, 0.2, Some(1), t0);
+        queue.push(1, 0.3, Some(2), t0);
+        queue.push(1, 0.4, Some(3), t0);
+        assert_eq!(queue.stats.metrics().pending, 1);
+        assert_eq!(queue.due(t0), [(1, 0.4, Some(3))]);
+        assert!(queue.due(t0 + VOLUME_INTERVAL).is_empty());
+
+        let metrics = queue.stats.metrics();
+        assert_eq!((metrics.coalesced, metrics.applied), (2, 1));
+    }
+
+    #[test]
+    fn drops_older_timestamps_until_the_window_ends() {
+        let mut queue = queue();
+        let t0 = Instant::now();
+        queue.push(1, 0.8, Some(50), t0);
+        queue.push(1, 0.1, Some(40), t0);
+        assert_eq!(queue.due(t0), [(1, 0.8, Some(50))]);
+
+        // Older than the one just applied
+        queue.push(1, 0.2, Some(45), t0 + ms(500));
+        assert!(queue.due(t0 + ms(500)).is_empty());
+        assert_eq!(queue.stats.metrics().stale, 2);
+
+        // Past the window it may be another client's clock
+        queue.push(1, 0.3, Some(10), t0 + STALE_WINDOW);
+        assert_eq!(queue.due(t0 + STALE_WINDOW), [(1, 0.3, Some(10))]);
+        // Untimestamped commands are never stale
+        queue.push(1, 0.4, None, t0 + STALE_WINDOW);
+        assert_eq!(queue.due(t0 + STALE_WINDOW + VOLUME_INTERVAL), [(1, 0.4, None)]);
+        assert_eq!(queue.stats.metrics().stale, 2);
+    }
+}
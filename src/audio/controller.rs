@@ -1,15 +1,46 @@
-use crate::models::device::AudioDevice;
+use crate::models::device::{AudioDevice, DefaultNodes, DeviceState, VolumeChange, VolumeScale};
 use anyhow::Result;
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
+/// Highest volume accepted for a device with boost enabled, as `wpctl
+/// set-volume` would take it (150%).
+pub const MAX_VOLUME: f32 = 1.5;
+
+/// Highest volume accepted for a device without boost.
+pub const UNITY_VOLUME: f32 = 1.0;
+
+/// Volumes closer than this are the same; reading one back from PipeWire's
+/// linear channel volumes doesn't give exactly what was set.
+pub const VOLUME_EPSILON: f32 = 0.001;
+
+// How long a requested volume stands in for the current one while PipeWire
+// hasn't confirmed it; covers the coalescing delay with room to spare
+const REQUEST_TTL: Duration = Duration::from_secs(1);
+
+struct Requested {
+    volume: f32,
+    at: Instant,
+}
+
+/// The server's view of every audio node, kept current by the event loop
+/// from what PipeWire confirms. REST and WebSocket handlers read volumes,
+/// mute and state from here.
 pub struct AudioController {
     devices: HashMap<u32, AudioDevice>,
+    // Volumes sent to PipeWire and not confirmed yet
+    requested: HashMap<u32, Requested>,
+    defaults: DefaultNodes,
+    revision: u64,
 }
 
 impl AudioController {
     pub fn new() -> Result<Self> {
         Ok(Self {
             devices: HashMap::new(),
+            requested: HashMap::new(),
+            defaults: DefaultNodes::default(),
+            revision: 0,
         })
     }
 
@@ -19,6 +50,100 @@ impl AudioController {
 
     pub fn remove_device(&mut self, id: u32) {
         self.devices.remove(&id);
+        self.requested.remove(&id);
+    }
+
+    /// Records a volume PipeWire confirmed.
+    pub fn set_volume(&mut self, id: u32, volume: f32) {
+        if self.requested.get(&id).is_some_and(|r| (r.volume - volume).abs() < VOLUME_EPSILON) {
+            self.requested.remove(&id);
+        }
+        if let Some(device) = self.devices.get_mut(&id) {
+            device.base_volume = volume;
+            for channel in &mut device.channels {
+                channel.volume = volume;
+            }
+        }
+    }
+
+    pub fn volume(&self, id: u32) -> Option<f32> {
+        self.devices.get(&id).map(|d| d.base_volume)
+    }
+
+    /// The highest volume device `id` accepts: 100%, or 150% with boost.
+    pub fn max_volume(&self, id: u32) -> Option<f32> {
+        self.devices
+            .get(&id)
+            .map(|d| if d.boost { MAX_VOLUME } else { UNITY_VOLUME })
+    }
+
+    /// The volume relative changes start from: the last one requested while
+    /// PipeWire hasn't confirmed it, so quick steps add up, else the current one.
+    fn target_volume(&self, id: u32) -> Option<f32> {
+        let current = self.volume(id)?;
+        Some(
+            self.requested
+                .get(&id)
+                .filter(|r| r.at.elapsed() < REQUEST_TTL)
+                .map_or(current, |r| r.volume),
+        )
+    }
+
+    /// The absolute volume a change amounts to for device `id`, clamped to
+    /// its allowed range. Absolute values and steps are read in `scale`.
+    /// `None` if there is no such device.
+    pub fn resolve_volume(&self, id: u32, change: VolumeChange, scale: VolumeScale) -> Option<f32> {
+        let current = self.target_volume(id)?;
+        let volume = match change.in_scale(scale) {
+            VolumeChange::Set(value) => scale.to_cubic(value),
+            VolumeChange::Step(step) => scale.to_cubic(scale.express(current) + step),
+            // wpctl volumes are cubic: amplitude goes with the cube
+            VolumeChange::Decibels(db) => current * 10f32.powf(db / 60.0),
+        };
+        Some(volume.clamp(0.0, self.max_volume(id)?))
+    }
+
+    /// Resolves a change like `resolve_volume` and remembers the result as
+    /// requested, for the caller to send to PipeWire.
+    pub fn request_volume(&mut self, id: u32, change: VolumeChange, scale: VolumeScale) -> Option<f32> {
+        let volume = self.resolve_volume(id, change, scale)?;
+        self.requested.insert(id, Requested { volume, at: Instant::now() });
+        Some(volume)
+    }
+
+    pub fn set_boost(&mut self, id: u32, boost: bool) {
+        if let Some(device) = self.devices.get_mut(&id) {
+            device.boost = boost;
+        }
+    }
+
+    pub fn set_state(&mut self, id: u32, state: DeviceState) {
+        if let Some(device) = self.devices.get_mut(&id) {
+            device.state = state;
+        }
+    }
+
+    pub fn set_mute(&mut self, id: u32, muted: bool) {
+        if let Some(device) = self.devices.get_mut(&id) {
+            device.muted = muted;
+        }
+    }
+
+    pub fn defaults(&self) -> &DefaultNodes {
+        &self.defaults
+    }
+
+    pub fn set_defaults(&mut self, defaults: DefaultNodes) {
+        self.defaults = defaults;
+    }
+
+    /// Revision of the last device or graph event applied.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn set_revision(&mut self, revision: u64) {
+        self.revision = revision;
     }
 
     pub fn list_devices(&self) -> Vec<AudioDevice> {
@@ -29,3 +154,65 @@ impl AudioController {
         self.devices.get(&id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::device::{Channel, DeviceType};
+    use std::collections::BTreeMap;
+
+    fn controller(volume: f32) -> AudioController {
+        let mut audio = AudioController::new().unwrap();
+        audio.add_device(AudioDevice {
+            id: 1,
+            name: "speakers".to_string(),
+            description: "Speakers".to_string(),
+            device_type: DeviceType::Sink,
+            state: DeviceState::Idle,
+            channels: vec![Channel { index: 0, name: "Master".to_string(), volume }],
+            muted: false,
+            base_volume: volume,
+            boost: false,
+            properties: BTreeMap::new(),
+        });
+        audio
+    }
+
+    #[test]
+    fn quick_steps_add_up_before_confirmation() {
+        let mut audio = controller(0.5);
+        let step = VolumeChange::Step(0.05);
+        for _ in 0..3 {
+            audio.request_volume(1, step, VolumeScale::Cubic).unwrap();
+        }
+        let target = audio.request_volume(1, VolumeChange::Step(0.0), VolumeScale::Cubic).unwrap();
+        assert!((target - 0.65).abs() < VOLUME_EPSILON);
+
+        // An older value confirmed on the way keeps the pending target
+        audio.set_volume(1, 0.55);
+        let target = audio.resolve_volume(1, VolumeChange::Step(0.0), VolumeScale::Cubic).unwrap();
+        assert!((target - 0.65).abs() < VOLUME_EPSILON);
+
+        audio.set_volume(1, 0.65);
+        assert!(audio.requested.is_empty());
+    }
+
+    #[test]
+    fn negative_db_levels_are_absolute() {
+        let audio = controller(0.5);
+        let level = audio.resolve_volume(1, "-6".parse().unwrap(), VolumeScale::Db).unwrap();
+        assert!((VolumeScale::Db.express(level) + 6.0).abs() < 0.01);
+        // The suffixed form still steps down from the current volume
+        let step = audio.resolve_volume(1, "-6 dB".parse().unwrap(), VolumeScale::Db).unwrap();
+        assert!((VolumeScale::Db.express(step) - (VolumeScale::Db.express(0.5) - 6.0)).abs() < 0.01);
+    }
+
+    #[test]
+    fn steps_are_clamped_without_boost() {
+        let mut audio = controller(0.98);
+        assert_eq!(audio.request_volume(1, VolumeChange::Step(0.05), VolumeScale::Cubic), Some(UNITY_VOLUME));
+        audio.set_boost(1, true);
+        let boosted = audio.request_volume(1, VolumeChange::Step(0.05), VolumeScale::Cubic).unwrap();
+        assert!((boosted - 1.05).abs() < VOLUME_EPSILON);
+    }
+}
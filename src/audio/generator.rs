@@ -0,0 +1,281 @@
+use anyhow::{bail, Result};
+use std::f32::consts::TAU;
+use std::path::Path;
+
+/// Produces interleaved f32 frames for a playback stream.
+pub trait SampleSource: Send {
+    fn channels(&self) -> u32;
+    fn rate(&self) -> u32;
+
+    /// Fills `out` with whole frames and returns how many were written.
+    /// Returning 0 ends playback.
+    fn fill(&mut self, out: &mut [f32]) -> usize;
+}
+
+impl<S: SampleSource + ?Sized> SampleSource for Box<S> {
+    fn channels(&self) -> u32 {
+        (**self).channels()
+    }
+
+    fn rate(&self) -> u32 {
+        (**self).rate()
+    }
+
+    fn fill(&mut self, out: &mut [f32]) -> usize {
+        (**self).fill(out)
+    }
+}
+
+pub struct Sine {
+    channels: u32,
+    rate: u32,
+    step: f32,
+    phase: f32,
+    level: f32,
+}
+
+impl Sine {
+    pub fn new(channels: u32, rate: u32, frequency: f32, level: f32) -> Self {
+        Self {
+            channels,
+            rate,
+            step: TAU * frequency / rate as f32,
+            phase: 0.0,
+            level,
+        }
+    }
+}
+
+impl SampleSource for Sine {
+    fn channels(&self) -> u32 {
+        self.channels
+    }
+
+    fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    fn fill(&mut self, out: &mut [f32]) -> usize {
+        for frame in out.chunks_exact_mut(self.channels as usize) {
+            frame.fill(self.phase.sin() * self.level);
+            self.phase = (self.phase + self.step) % TAU;
+        }
+        out.len() / self.channels as usize
+    }
+}
+
+// Paul Kellett's economy pink noise filter fed by xorshift white noise
+struct PinkFilter {
+    rng: u32,
+    b: [f32; 3],
+}
+
+impl PinkFilter {
+    fn new(seed: u32) -> Self {
+        Self {
+            rng: seed.max(1),
+            b: [0.0; 3],
+        }
+    }
+
+    fn next(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        let white = self.rng as f32 / u32::MAX as f32 * 2.0 - 1.0;
+
+        self.b[0] = 0.99765 * self.b[0] + white * 0.0990460;
+        self.b[1] = 0.96300 * self.b[1] + white * 0.2965164;
+        self.b[2] = 0.57000 * self.b[2] + white * 1.0526913;
+        (self.b[0] + self.b[1] + self.b[2] + white * 0.1848) * 0.25
+    }
+}
+
+pub struct PinkNoise {
+    channels: u32,
+    rate: u32,
+    filter: PinkFilter,
+    level: f32,
+}
+
+impl PinkNoise {
+    pub fn new(channels: u32, rate: u32, level: f32) -> Self {
+        Self {
+            channels,
+            rate,
+            filter: PinkFilter::new(0x9e37_79b9),
+            level,
+        }
+    }
+}
+
+impl SampleSource for PinkNoise {
+    fn channels(&self) -> u32 {
+        self.channels
+    }
+
+    fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    fn fill(&mut self, out: &mut [f32]) -> usize {
+        for frame in out.chunks_exact_mut(self.channels as usize) {
+            frame.fill(self.filter.next() * self.level);
+        }
+        out.len() / self.channels as usize
+    }
+}
+
+/// Speaker identification: a pink noise burst on one channel at a time,
+/// cycling through all channels in order.
+pub struct SpeakerSweep {
+    channels: u32,
+    rate: u32,
+    filter: PinkFilter,
+    level: f32,
+    burst_frames: u64,
+    period_frames: u64,
+    position: u64,
+}
+
+impl SpeakerSweep {
+    const BURST_SECS: f32 = 1.0;
+    const GAP_SECS: f32 = 0.5;
+
+    pub fn new(channels: u32, rate: u32, level: f32) -> Self {
+        let burst_frames = (rate as f32 * Self::BURST_SECS) as u64;
+        Self {
+            channels,
+            rate,
+            filter: PinkFilter::new(0x2545_f491),
+            level,
+            burst_frames,
+            period_frames: burst_frames + (rate as f32 * Self::GAP_SECS) as u64,
+            position: 0,
+        }
+    }
+
+    /// Channel currently sounding, if any.
+    pub fn active_channel(&self) -> Option<u32> {
+        let in_period = self.position % self.period_frames;
+        (in_period < self.burst_frames)
+            .then(|| ((self.position / self.period_frames) % self.channels as u64) as u32)
+    }
+}
+
+impl SampleSource for SpeakerSweep {
+    fn channels(&self) -> u32 {
+        self.channels
+    }
+
+    fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    fn fill(&mut self, out: &mut [f32]) -> usize {
+        for frame in out.chunks_exact_mut(self.channels as usize) {
+            frame.fill(0.0);
+            if let Some(channel) = self.active_channel() {
+                frame[channel as usize] = self.filter.next() * self.level;
+            }
+            self.position += 1;
+        }
+        out.len() / self.channels as usize
+    }
+}
+
+/// Fully decoded audio held in memory, e.g. an uploaded WAV file.
+pub struct Samples {
+    channels: u32,
+    rate: u32,
+    data: Vec<f32>,
+    position: usize,
+    level: f32,
+}
+
+impl Samples {
+    pub fn from_wav(path: &Path, level: f32) -> Result<Self> {
+        let mut reader = hound::WavReader::open(path)?;
+        let spec = reader.spec();
+        if spec.channels == 0 || spec.sample_rate == 0 {
+            bail!("Invalid WAV header");
+        }
+
+        let data = match spec.sample_format {
+            hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+            hound::SampleFormat::Int => {
+                let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|s| s.map(|v| v as f32 / scale))
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+        };
+
+        Ok(Self {
+            channels: spec.channels as u32,
+            rate: spec.sample_rate,
+            data,
+            position: 0,
+            level,
+        })
+    }
+}
+
+impl SampleSource for Samples {
+    fn channels(&self) -> u32 {
+        self.channels
+    }
+
+    fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    fn fill(&mut self, out: &mut [f32]) -> usize {
+        let channels = self.channels as usize;
+        let remaining = &self.data[self.position..];
+        let n = out.len().min(remaining.len()) / channels * channels;
+        for (o, s) in out[..n].iter_mut().zip(remaining) {
+            *o = s * self.level;
+        }
+        self.position += n;
+        n / channels
+    }
+}
+
+/// Stops the inner source after a fixed number of frames.
+pub struct Limited<S> {
+    inner: S,
+    remaining: u64,
+}
+
+impl<S: SampleSource> Limited<S> {
+    pub fn new(inner: S, max_frames: u64) -> Self {
+        Self {
+            inner,
+            remaining: max_frames,
+        }
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining == 0
+    }
+}
+
+impl<S: SampleSource> SampleSource for Limited<S> {
+    fn channels(&self) -> u32 {
+        self.inner.channels()
+    }
+
+    fn rate(&self) -> u32 {
+        self.inner.rate()
+    }
+
+    fn fill(&mut self, out: &mut [f32]) -> usize {
+        let channels = self.inner.channels() as usize;
+        let max = (self.remaining as usize).min(out.len() / channels);
+        let n = self.inner.fill(&mut out[..max * channels]);
+        self.remaining -= n as u64;
+        n
+    }
+}
@@ -0,0 +1,153 @@
+use crate::audio::pipewire::PipeWireHandler;
+use crate::audio::stream::{CaptureRequest, SAMPLE_RATE};
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::sync::Arc;
+use std::thread;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::{debug, info};
+
+// Rates reachable from SAMPLE_RATE by integer decimation, highest first
+const RATES: &[u32] = &[48000, 24000, 16000, 12000, 8000];
+const BITS_PER_SAMPLE: u32 = 16;
+
+// Roughly 20ms per capture quantum, so the two queues bound latency to ~0.5s
+const CAPTURE_QUEUE: usize = 8;
+const SEND_QUEUE: usize = 16;
+
+pub const MIN_BITRATE_KBPS: u32 = 128;
+pub const MAX_BITRATE_KBPS: u32 = 1536;
+
+/// Wire format of a listen stream: interleaved signed 16-bit little-endian PCM.
+#[derive(Debug, Clone, Serialize)]
+pub struct ListenFormat {
+    pub encoding: &'static str,
+    pub sample_rate: u32,
+    pub channels: u32,
+    pub bitrate_kbps: u32,
+}
+
+impl ListenFormat {
+    /// Picks the highest sample rate whose bitrate fits within `kbps`.
+    pub fn for_bitrate(kbps: u32, channels: u32) -> Result<Self> {
+        if !(1..=2).contains(&channels) {
+            bail!("Unsupported channel count: {}", channels);
+        }
+        let kbps = kbps.clamp(MIN_BITRATE_KBPS, MAX_BITRATE_KBPS);
+        let sample_rate = RATES
+            .iter()
+            .copied()
+            .find(|rate| rate * channels * BITS_PER_SAMPLE <= kbps * 1000)
+            .unwrap_or(RATES[RATES.len() - 1]);
+
+        Ok(Self {
+            encoding: "s16le",
+            sample_rate,
+            channels,
+            bitrate_kbps: sample_rate * channels * BITS_PER_SAMPLE / 1000,
+        })
+    }
+}
+
+pub struct ListenTarget {
+    pub node_id: u32,
+    pub node_name: String,
+    pub is_sink: bool,
+}
+
+/// A running capture feeding encoded PCM chunks. Dropping it stops the
+/// underlying stream.
+pub struct ListenSession {
+    pub id: u32,
+    pub receiver: mpsc::Receiver<Vec<u8>>,
+    pw_handler: Arc<PipeWireHandler>,
+}
+
+impl Drop for ListenSession {
+    fn drop(&mut self) {
+        self.pw_handler.stop_stream(self.id);
+    }
+}
+
+pub fn start(
+    pw_handler: Arc<PipeWireHandler>,
+    target: ListenTarget,
+    format: &ListenFormat,
+) -> ListenSession {
+    let (capture_tx, capture_rx) = crossbeam_channel::bounded::<Vec<f32>>(CAPTURE_QUEUE);
+    let (send_tx, send_rx) = mpsc::channel(SEND_QUEUE);
+
+    let id = pw_handler.allocate_stream_id();
+    pw_handler.start_capture(id, CaptureRequest {
+        target_id: target.node_id,
+        target_name: target.node_name.clone(),
+        capture_sink: target.is_sink,
+        channels: format.channels,
+        sender: capture_tx,
+    });
+    info!("Listen stream {} started on {} ({} kbps)", id, target.node_name, format.bitrate_kbps);
+
+    let mut encoder = PcmEncoder::new(format.channels, SAMPLE_RATE / format.sample_rate);
+    thread::spawn(move || {
+        let mut dropped = 0u64;
+        // Ends when the capture stream is torn down and the channel closes
+        while let Ok(samples) = capture_rx.recv() {
+            let chunk = encoder.encode(&samples);
+            if chunk.is_empty() {
+                continue;
+            }
+            match send_tx.try_send(chunk) {
+                Ok(()) => {}
+                // A slow client loses audio rather than building up delay
+                Err(TrySendError::Full(_)) => dropped += 1,
+                Err(TrySendError::Closed(_)) => break,
+            }
+        }
+        debug!("Listen stream {} ended, {} chunks dropped", id, dropped);
+    });
+
+    ListenSession {
+        id,
+        receiver: send_rx,
+        pw_handler,
+    }
+}
+
+/// Decimates f32 frames by an integer factor (box filter) and quantizes to
+/// s16le. Partial frames carry over between calls.
+struct PcmEncoder {
+    channels: usize,
+    factor: usize,
+    acc: Vec<f32>,
+    count: usize,
+}
+
+impl PcmEncoder {
+    fn new(channels: u32, factor: u32) -> Self {
+        Self {
+            channels: channels as usize,
+            factor: factor.max(1) as usize,
+            acc: vec![0.0; channels as usize],
+            count: 0,
+        }
+    }
+
+    fn encode(&mut self, samples: &[f32]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(samples.len() * 2 / self.factor + self.channels * 2);
+        for frame in samples.chunks_exact(self.channels) {
+            for (acc, s) in self.acc.iter_mut().zip(frame) {
+                *acc += s;
+            }
+            self.count += 1;
+            if self.count == self.factor {
+                for acc in self.acc.iter_mut() {
+                    let v = (*acc / self.factor as f32).clamp(-1.0, 1.0);
+                    out.extend_from_slice(&((v * i16::MAX as f32) as i16).to_le_bytes());
+                    *acc = 0.0;
+                }
+                self.count = 0;
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,120 @@
+use crate::audio::pipewire::PipeWireHandler;
+use crate::audio::stream::{CaptureRequest, SAMPLE_RATE};
+use crate::utils::broadcast::EventBroadcaster;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+use tracing::{debug, info};
+
+// Frames per meter update, ~50ms
+const METER_FRAMES: usize = (SAMPLE_RATE / 20) as usize;
+const CAPTURE_QUEUE: usize = 8;
+
+pub struct MeterTarget {
+    pub node_id: u32,
+    pub node_name: String,
+    pub is_sink: bool,
+    pub channels: u32,
+}
+
+struct Meter {
+    stream_id: u32,
+    refs: usize,
+}
+
+/// Peak meters shared between subscribers: one capture stream per metered
+/// node, running while at least one `MeterLease` for it is alive.
+pub struct MeterManager {
+    active: Mutex<HashMap<u32, Meter>>,
+    broadcaster: Arc<EventBroadcaster>,
+    pw_handler: Arc<PipeWireHandler>,
+}
+
+/// Keeps a node's meter running. Dropping the last lease stops the stream.
+pub struct MeterLease {
+    node_id: u32,
+    manager: Arc<MeterManager>,
+}
+
+impl Drop for MeterLease {
+    fn drop(&mut self) {
+        self.manager.release(self.node_id);
+    }
+}
+
+impl MeterManager {
+    pub fn new(broadcaster: Arc<EventBroadcaster>, pw_handler: Arc<PipeWireHandler>) -> Self {
+        Self {
+            active: Mutex::new(HashMap::new()),
+            broadcaster,
+            pw_handler,
+        }
+    }
+
+    pub fn acquire(self: &Arc<Self>, target: MeterTarget) -> MeterLease {
+        let node_id = target.node_id;
+        let mut active = self.active.lock();
+        match active.get_mut(&node_id) {
+            Some(meter) => meter.refs += 1,
+            None => {
+                let stream_id = self.start(target);
+                active.insert(node_id, Meter { stream_id, refs: 1 });
+            }
+        }
+        MeterLease {
+            node_id,
+            manager: self.clone(),
+        }
+    }
+
+    fn release(&self, node_id: u32) {
+        let mut active = self.active.lock();
+        let Some(meter) = active.get_mut(&node_id) else {
+            return;
+        };
+        meter.refs -= 1;
+        if meter.refs == 0 {
+            let stream_id = meter.stream_id;
+            active.remove(&node_id);
+            self.pw_handler.stop_stream(stream_id);
+            info!("Meter on node {} stopped", node_id);
+        }
+    }
+
+    fn start(&self, target: MeterTarget) -> u32 {
+        let (capture_tx, capture_rx) = crossbeam_channel::bounded::<Vec<f32>>(CAPTURE_QUEUE);
+        let stream_id = self.pw_handler.allocate_stream_id();
+        self.pw_handler.start_capture(stream_id, CaptureRequest {
+            target_id: target.node_id,
+            target_name: target.node_name.clone(),
+            capture_sink: target.is_sink,
+            channels: target.channels,
+            sender: capture_tx,
+        });
+        info!("Meter on node {} started ({} channels)", target.node_name, target.channels);
+
+        let node_id = target.node_id;
+        let channels = target.channels as usize;
+        let broadcaster = self.broadcaster.clone();
+        thread::spawn(move || {
+            let mut peaks = vec![0.0f32; channels];
+            let mut frames = 0;
+            // Ends when the capture stream is torn down and the channel closes
+            while let Ok(samples) = capture_rx.recv() {
+                for frame in samples.chunks_exact(channels) {
+                    for (peak, s) in peaks.iter_mut().zip(frame) {
+                        *peak = peak.max(s.abs());
+                    }
+                    frames += 1;
+                    if frames == METER_FRAMES {
+                        broadcaster.send_meter(node_id, std::mem::replace(&mut peaks, vec![0.0; channels]));
+                        frames = 0;
+                    }
+                }
+            }
+            debug!("Meter stream {} ended", stream_id);
+        });
+        stream_id
+    }
+}
@@ -0,0 +1,275 @@
+use crate::audio::generator::SampleSource;
+use crate::audio::pipewire::PipeWireHandler;
+use crate::audio::stream::{VirtualSourceRequest, SAMPLE_RATE};
+use anyhow::{bail, Result};
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tracing::info;
+
+// Audio is held back this long before playout starts (and again after an
+// underrun) so that uneven WebSocket delivery doesn't cause dropouts
+const TARGET_LATENCY_MS: usize = 80;
+// Beyond this the buffer is trimmed back to the target to keep delay bounded
+const MAX_LATENCY_MS: usize = 300;
+
+pub struct MicFormat {
+    pub sample_rate: u32,
+    pub channels: u32,
+}
+
+impl MicFormat {
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=2).contains(&self.channels) {
+            bail!("Unsupported channel count: {}", self.channels);
+        }
+        if !(8000..=192000).contains(&self.sample_rate) {
+            bail!("Unsupported sample rate: {}", self.sample_rate);
+        }
+        Ok(())
+    }
+}
+
+/// A browser microphone exposed as a virtual `Audio/Source` node. Dropping
+/// the session removes the node.
+pub struct MicSession {
+    pub id: u32,
+    channels: usize,
+    // Bytes of a frame split across messages, completed by the next push
+    partial: Vec<u8>,
+    resampler: LinearResampler,
+    buffer: Arc<Mutex<JitterBuffer>>,
+    pw_handler: Arc<PipeWireHandler>,
+}
+
+impl MicSession {
+    /// Feeds interleaved s16le frames at the client's sample rate. Frames
+    /// may be split across calls.
+    pub fn push(&mut self, data: &[u8]) {
+        self.partial.extend_from_slice(data);
+        let frame_bytes = self.channels * 2;
+        let whole = self.partial.len() / frame_bytes * frame_bytes;
+        let samples: Vec<f32> = self
+            .partial
+            .drain(..whole)
+            .as_slice()
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+            .collect();
+        let resampled = self.resampler.process(&samples);
+        self.buffer.lock().push(&resampled);
+    }
+}
+
+impl Drop for MicSession {
+    fn drop(&mut self) {
+        self.buffer.lock().closed = true;
+        self.pw_handler.stop_stream(self.id);
+        let buffer = self.buffer.lock();
+        info!(
+            "Microphone source {} removed ({} underruns, {} overruns)",
+            self.id, buffer.underruns, buffer.overruns
+        );
+    }
+}
+
+pub fn start(pw_handler: Arc<PipeWireHandler>, description: &str, format: MicFormat) -> Result<MicSession> {
+    format.validate()?;
+
+    let channels = format.channels as usize;
+    let frames_per_ms = SAMPLE_RATE as usize / 1000;
+    let buffer = Arc::new(Mutex::new(JitterBuffer::new(
+        channels,
+        TARGET_LATENCY_MS * frames_per_ms,
+        MAX_LATENCY_MS * frames_per_ms,
+    )));
+
+    let id = pw_handler.allocate_stream_id();
+    pw_handler.start_virtual_source(id, VirtualSourceRequest {
+        description: description.to_string(),
+        source: Box::new(MicSource {
+            channels: format.channels,
+            buffer: buffer.clone(),
+        }),
+    });
+    info!("Microphone source {} created: {} ({} Hz)", id, description, format.sample_rate);
+
+    Ok(MicSession {
+        id,
+        channels,
+        partial: Vec::new(),
+        resampler: LinearResampler::new(channels, format.sample_rate, SAMPLE_RATE),
+        buffer,
+        pw_handler,
+    })
+}
+
+/// Playout side of the jitter buffer, pulled by the PipeWire stream.
+struct MicSource {
+    channels: u32,
+    buffer: Arc<Mutex<JitterBuffer>>,
+}
+
+impl SampleSource for MicSource {
+    fn channels(&self) -> u32 {
+        self.channels
+    }
+
+    fn rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn fill(&mut self, out: &mut [f32]) -> usize {
+        self.buffer.lock().pull(out)
+    }
+}
+
+struct JitterBuffer {
+    samples: VecDeque<f32>,
+    channels: usize,
+    target_frames: usize,
+    max_frames: usize,
+    buffering: bool,
+    closed: bool,
+    underruns: u64,
+    overruns: u64,
+}
+
+impl JitterBuffer {
+    fn new(channels: usize, target_frames: usize, max_frames: usize) -> Self {
+        Self {
+            samples: VecDeque::new(),
+            channels,
+            target_frames,
+            max_frames,
+            buffering: true,
+            closed: false,
+            underruns: 0,
+            overruns: 0,
+        }
+    }
+
+    fn push(&mut self, samples: &[f32]) {
+        self.samples.extend(samples);
+        if self.samples.len() > self.max_frames * self.channels {
+            let excess = self.samples.len() - self.target_frames * self.channels;
+            self.samples.drain(..excess);
+            self.overruns += 1;
+        }
+    }
+
+    /// Always produces whole frames of audio or silence until closed.
+    fn pull(&mut self, out: &mut [f32]) -> usize {
+        if self.closed {
+            return 0;
+        }
+        let frames = out.len() / self.channels;
+        let out = &mut out[..frames * self.channels];
+
+        if self.buffering {
+            if self.samples.len() < self.target_frames * self.channels {
+                out.fill(0.0);
+                return frames;
+            }
+            self.buffering = false;
+        }
+
+        let n = out.len().min(self.samples.len());
+        for (o, s) in out.iter_mut().zip(self.samples.drain(..n)) {
+            *o = s;
+        }
+        if n < out.len() {
+            out[n..].fill(0.0);
+            self.underruns += 1;
+            self.buffering = true;
+        }
+        frames
+    }
+}
+
+/// Linear interpolation between consecutive frames; adequate for speech and
+/// cheap enough to run per WebSocket message.
+struct LinearResampler {
+    channels: usize,
+    step: f64,
+    position: f64, // relative to `last`, which sits at index 0
+    last: Vec<f32>,
+}
+
+impl LinearResampler {
+    fn new(channels: usize, from_rate: u32, to_rate: u32) -> Self {
+        Self {
+            channels,
+            step: from_rate as f64 / to_rate as f64,
+            position: 0.0,
+            last: vec![0.0; channels],
+        }
+    }
+
+    fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        let frames = input.len() / self.channels;
+        if frames == 0 {
+            return Vec::new();
+        }
+        let frame = |i: usize| -> &[f32] {
+            if i == 0 {
+                &self.last
+            } else {
+                &input[(i - 1) * self.channels..i * self.channels]
+            }
+        };
+
+        let mut out = Vec::with_capacity(((frames as f64 / self.step) as usize + 1) * self.channels);
+        while self.position < frames as f64 {
+            let i = self.position as usize;
+            let frac = (self.position - i as f64) as f32;
+            for (a, b) in frame(i).iter().zip(frame(i + 1)) {
+                out.push(a + (b - a) * frac);
+            }
+            self.position += self.step;
+        }
+
+        self.position -= frames as f64;
+        self.last = input[(frames - 1) * self.channels..frames * self.channels].to_vec();
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(channels: u32) -> MicSession {
+        let (tx, _rx) = crossbeam_channel::unbounded();
+        let pw_handler = Arc::new(PipeWireHandler::with_sender(tx));
+        start(pw_handler, "test", MicFormat { sample_rate: SAMPLE_RATE, channels }).unwrap()
+    }
+
+    fn buffered(session: &MicSession) -> Vec<f32> {
+        session.buffer.lock().samples.iter().copied().collect()
+    }
+
+    #[test]
+    fn frames_split_across_messages_are_kept() {
+        let pcm: Vec<u8> = (0..64i16).flat_map(|s| (s * 100).to_le_bytes()).collect();
+        let mut whole = session(2);
+        whole.push(&pcm);
+
+        let mut split = session(2);
+        for chunk in pcm.chunks(7) {
+            split.push(chunk);
+        }
+        assert_eq!(buffered(&split), buffered(&whole));
+        assert!(split.partial.is_empty());
+
+        split.push(&pcm[..3]);
+        assert_eq!(split.partial.len(), 3);
+    }
+
+    #[test]
+    fn rejects_unsupported_formats() {
+        assert!(MicFormat { sample_rate: 48000, channels: 3 }.validate().is_err());
+        assert!(MicFormat { sample_rate: 4000, channels: 1 }.validate().is_err());
+        assert!(MicFormat { sample_rate: 44100, channels: 2 }.validate().is_ok());
+    }
+}
@@ -1,2 +1,12 @@
+pub mod boost;
+pub mod coalesce;
 pub mod controller;
+pub mod generator;
+pub mod listen;
+pub mod meter;
+pub mod mic;
 pub mod pipewire;
+pub mod player;
+pub mod recorder;
+pub mod rtp;
+pub mod stream;
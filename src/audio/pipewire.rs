@@ -1,4 +1,6 @@
-use crate::models::device::{AudioDevice, Channel, DeviceState, DeviceType};
+use crate::audio::coalesce::{VolumeQueue, VolumeStats};
+use crate::audio::stream::{self, ActiveStream, CaptureRequest, PlaybackRequest, VirtualSourceRequest};
+use crate::models::device::{AudioDevice, Channel, DefaultNodes, DeviceState, DeviceType};
 use crate::models::graph::{Link, Port, PortDirection};
 use crossbeam_channel::{Receiver, Sender};
 use parking_lot::Mutex;
@@ -6,44 +8,82 @@ use pipewire as pw;
 use pipewire::context::Context;
 use pipewire::main_loop::MainLoop;
 use pipewire::types::ObjectType;
-use std::collections::HashSet;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::process::Command;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 use std::thread;
+use std::time::Instant;
 use tracing::{error, info};
 
+/// Prefix of the `node.name` of every stream this server creates.
+pub const NODE_NAME_PREFIX: &str = "pipewire-web-remote.";
+
 pub enum PwCommand {
     SetVolume(u32, f32, Option<u64>),
     SetMute(u32, bool),
-    CreateLink(u32, u32, u32, u32), // out_node, out_port, in_node, in_port
-    DeleteLink(u32),                // link_id
+    CreateLink(u32, u32, u32, u32),                // out_node, out_port, in_node, in_port
+    DeleteLink(u32),                               // link_id
+    MoveStream(u32, String),                       // stream node_id, target node.name
+    StartCapture(u32, CaptureRequest),             // stream_id, request
+    StartPlayback(u32, PlaybackRequest),           // stream_id, request
+    StartVirtualSource(u32, VirtualSourceRequest), // stream_id, request
+    StopStream(u32),                               // stream_id
 }
 
 pub enum PwEvent {
     DeviceAdded(AudioDevice),
     DeviceRemoved(u32),
     VolumeChanged(u32, f32, Option<u64>),
+    MuteChanged(u32, bool),
+    StateChanged(u32, DeviceState),
     PortAdded(Port),
     PortRemoved(u32),
     LinkAdded(Link),
     LinkRemoved(u32),
+    DefaultsChanged(DefaultNodes),
 }
 
 pub struct PipeWireHandler {
     sender: Sender<PwCommand>,
+    next_stream_id: AtomicU32,
+    volume_stats: Arc<VolumeStats>,
 }
 
 impl PipeWireHandler {
     pub fn new(event_sender: Sender<PwEvent>) -> anyhow::Result<Self> {
         let (cmd_sender, cmd_receiver) = crossbeam_channel::unbounded();
+        let volume_stats = Arc::new(VolumeStats::default());
+        let loop_stats = volume_stats.clone();
 
         thread::spawn(move || {
-            if let Err(e) = run_pipewire_loop(cmd_receiver, event_sender) {
+            if let Err(e) = run_pipewire_loop(cmd_receiver, event_sender, loop_stats) {
                 error!("PipeWire loop error: {}", e);
             }
         });
 
-        Ok(Self { sender: cmd_sender })
+        Ok(Self {
+            sender: cmd_sender,
+            next_stream_id: AtomicU32::new(1),
+            volume_stats,
+        })
+    }
+
+    /// A handler whose commands go to `sender` instead of a PipeWire loop.
+    #[cfg(test)]
+    pub fn with_sender(sender: Sender<PwCommand>) -> Self {
+        Self {
+            sender,
+            next_stream_id: AtomicU32::new(1),
+            volume_stats: Arc::new(VolumeStats::default()),
+        }
+    }
+
+    /// Counters for queued, coalesced and applied volume changes.
+    pub fn volume_stats(&self) -> &VolumeStats {
+        &self.volume_stats
     }
 
     pub fn set_volume(&self, id: u32, vol: f32, timestamp: Option<u64>) {
@@ -63,6 +103,32 @@ impl PipeWireHandler {
     pub fn delete_link(&self, link_id: u32) {
         let _ = self.sender.send(PwCommand::DeleteLink(link_id));
     }
+
+    /// Asks the session manager to route a stream to another node.
+    pub fn move_stream(&self, node_id: u32, target_name: &str) {
+        let _ = self.sender.send(PwCommand::MoveStream(node_id, target_name.to_string()));
+    }
+
+    /// Reserves an id for a stream started with `start_capture` or `start_playback`.
+    pub fn allocate_stream_id(&self) -> u32 {
+        self.next_stream_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub fn start_capture(&self, stream_id: u32, request: CaptureRequest) {
+        let _ = self.sender.send(PwCommand::StartCapture(stream_id, request));
+    }
+
+    pub fn start_playback(&self, stream_id: u32, request: PlaybackRequest) {
+        let _ = self.sender.send(PwCommand::StartPlayback(stream_id, request));
+    }
+
+    pub fn start_virtual_source(&self, stream_id: u32, request: VirtualSourceRequest) {
+        let _ = self.sender.send(PwCommand::StartVirtualSource(stream_id, request));
+    }
+
+    pub fn stop_stream(&self, stream_id: u32) {
+        let _ = self.sender.send(PwCommand::StopStream(stream_id));
+    }
 }
 
 fn get_real_volume(id: u32) -> (f32, bool) {
@@ -86,19 +152,101 @@ fn get_real_volume(id: u32) -> (f32, bool) {
     (1.0, false)
 }
 
+/// Default node values are JSON such as `{ "name": "alsa_output.pci-..." }`.
+fn default_node_name(value: &str) -> Option<String> {
+    serde_json::from_str::<serde_json::Value>(value)
+        .ok()?
+        .get("name")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Volume and mute from a node's `Props` param. PipeWire keeps linear
+/// channel volumes; the cube root gives the cubic volume `wpctl` shows.
+fn parse_props(pod: &pw::spa::pod::Pod) -> (Option<f32>, Option<bool>) {
+    use pw::spa::pod::{deserialize::PodDeserializer, Value, ValueArray};
+
+    let Ok((_, Value::Object(object))) = PodDeserializer::deserialize_any_from(pod.as_bytes()) else {
+        return (None, None);
+    };
+    let mut volume = None;
+    let mut muted = None;
+    for property in object.properties {
+        match (property.key, property.value) {
+            (pw::spa::sys::SPA_PROP_channelVolumes, Value::ValueArray(ValueArray::Float(volumes)))
+                if !volumes.is_empty() =>
+            {
+                let linear = volumes.iter().sum::<f32>() / volumes.len() as f32;
+                volume = Some(linear.max(0.0).cbrt());
+            }
+            (pw::spa::sys::SPA_PROP_mute, Value::Bool(mute)) => muted = Some(mute),
+            _ => {}
+        }
+    }
+    (volume, muted)
+}
+
+fn properties_of(props: &pw::spa::utils::dict::DictRef) -> BTreeMap<String, String> {
+    props
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+/// Runs `wpctl set-volume`, reporting the change back on success.
+fn apply_volume(id: u32, vol: f32, timestamp: Option<u64>, sender: &Sender<PwEvent>, stats: &VolumeStats) {
+    let vol_pct = format!("{}%", (vol * 100.0) as u32);
+    info!("EXEC: wpctl set-volume {} {}", id, vol_pct);
+    match Command::new("wpctl")
+        .arg("set-volume")
+        .arg(id.to_string())
+        .arg(vol_pct)
+        .output()
+    {
+        Ok(out) if out.status.success() => {
+            // Signal volume change back to main loop for broadcasting
+            let _ = sender.send(PwEvent::VolumeChanged(id, vol, timestamp));
+        }
+        Ok(out) => {
+            error!("wpctl error: {}", String::from_utf8_lossy(&out.stderr));
+            stats.record_failure();
+        }
+        Err(e) => {
+            error!("Failed to run wpctl: {}", e);
+            stats.record_failure();
+        }
+    }
+}
+
 fn run_pipewire_loop(
     cmd_receiver: Receiver<PwCommand>,
     event_sender: Sender<PwEvent>,
+    volume_stats: Arc<VolumeStats>,
 ) -> anyhow::Result<()> {
     pw::init();
     let mainloop = MainLoop::new(None)?;
     let context = Context::new(&mainloop)?;
     let core = context.connect(None)?;
-    let registry = core.get_registry()?;
+    let registry = Rc::new(core.get_registry()?);
+    let registry_weak = Rc::downgrade(&registry);
+    let streams: RefCell<HashMap<u32, ActiveStream>> = RefCell::new(HashMap::new());
 
     let sender_global = event_sender.clone();
     let sender_remove = event_sender.clone();
     let sender_cmd = event_sender.clone();
+    let sender_meta = event_sender.clone();
+    let sender_state = event_sender.clone();
+
+    // The "default" metadata object and its listener, kept alive while bound
+    let default_metadata: Rc<RefCell<Option<(pw::metadata::Metadata, pw::metadata::MetadataListener)>>> =
+        Rc::new(RefCell::new(None));
+    let default_metadata_global = default_metadata.clone();
+
+    // Bound audio nodes and their info listeners, which report state changes
+    let bound_nodes: Rc<RefCell<HashMap<u32, (pw::node::Node, pw::node::NodeListener)>>> =
+        Rc::new(RefCell::new(HashMap::new()));
+    let bound_nodes_global = bound_nodes.clone();
+    let bound_nodes_remove = bound_nodes.clone();
 
     let tracked_ids = Arc::new(Mutex::new(HashSet::new()));
     let tracked_ids_global = tracked_ids.clone();
@@ -152,9 +300,51 @@ fn run_pipewire_loop(
                             channels,
                             muted,
                             base_volume: vol,
+                            boost: false,
+                            properties: properties_of(props),
                         };
 
                         let _ = sender_global.send(PwEvent::DeviceAdded(device));
+
+                        let Some(registry) = registry_weak.upgrade() else {
+                            return;
+                        };
+                        let node: pw::node::Node = match registry.bind(global) {
+                            Ok(node) => node,
+                            Err(e) => {
+                                error!("Failed to bind node {}: {}", id, e);
+                                return;
+                            }
+                        };
+                        let sender = sender_state.clone();
+                        let sender_props = sender_state.clone();
+                        let last_state = RefCell::new(DeviceState::Idle);
+                        let listener = node
+                            .add_listener_local()
+                            .info(move |info| {
+                                let state = match info.state() {
+                                    pw::node::NodeState::Running => DeviceState::Running,
+                                    pw::node::NodeState::Suspended => DeviceState::Suspended,
+                                    _ => DeviceState::Idle,
+                                };
+                                if last_state.replace(state) != state {
+                                    let _ = sender.send(PwEvent::StateChanged(id, state));
+                                }
+                            })
+                            // Volume and mute set elsewhere: pavucontrol, media keys, the session manager
+                            .param(move |_, _, _, _, param| {
+                                let Some(param) = param else { return };
+                                let (volume, muted) = parse_props(param);
+                                if let Some(volume) = volume {
+                                    let _ = sender_props.send(PwEvent::VolumeChanged(id, volume, None));
+                                }
+                                if let Some(muted) = muted {
+                                    let _ = sender_props.send(PwEvent::MuteChanged(id, muted));
+                                }
+                            })
+                            .register();
+                        node.subscribe_params(&[pw::spa::param::ParamType::Props]);
+                        bound_nodes_global.borrow_mut().insert(id, (node, listener));
                     }
                     ObjectType::Port => {
                         let id = global.id;
@@ -176,6 +366,8 @@ fn run_pipewire_loop(
                             node_id,
                             name: props.get("port.name").unwrap_or("").to_string(),
                             direction,
+                            channel: props.get("audio.channel").map(str::to_string),
+                            properties: properties_of(props),
                         };
                         let _ = sender_global.send(PwEvent::PortAdded(port));
                     }
@@ -209,11 +401,50 @@ fn run_pipewire_loop(
                         };
                         let _ = sender_global.send(PwEvent::LinkAdded(link));
                     }
+                    ObjectType::Metadata => {
+                        if props.get("metadata.name") != Some("default") {
+                            return;
+                        }
+                        let Some(registry) = registry_weak.upgrade() else {
+                            return;
+                        };
+                        let metadata: pw::metadata::Metadata = match registry.bind(global) {
+                            Ok(metadata) => metadata,
+                            Err(e) => {
+                                error!("Failed to bind default metadata: {}", e);
+                                return;
+                            }
+                        };
+
+                        let sender = sender_meta.clone();
+                        let defaults = RefCell::new(DefaultNodes::default());
+                        let listener = metadata
+                            .add_listener_local()
+                            .property(move |_subject, key, _type, value| {
+                                let mut defaults = defaults.borrow_mut();
+                                let before = defaults.clone();
+                                let name = value.and_then(default_node_name);
+                                match key {
+                                    Some("default.audio.sink") => defaults.audio_sink = name,
+                                    Some("default.audio.source") => defaults.audio_source = name,
+                                    // A missing key means all properties were cleared
+                                    None => *defaults = DefaultNodes::default(),
+                                    _ => return 0,
+                                }
+                                if *defaults != before {
+                                    let _ = sender.send(PwEvent::DefaultsChanged(defaults.clone()));
+                                }
+                                0
+                            })
+                            .register();
+                        *default_metadata_global.borrow_mut() = Some((metadata, listener));
+                    }
                     _ => {}
                 }
             }
         })
         .global_remove(move |id| {
+            bound_nodes_remove.borrow_mut().remove(&id);
             if tracked_ids_remove.lock().remove(&id) {
                 let _ = sender_remove.send(PwEvent::DeviceRemoved(id));
                 let _ = sender_remove.send(PwEvent::PortRemoved(id));
@@ -223,35 +454,30 @@ fn run_pipewire_loop(
         .register();
 
     let loop_ = mainloop.loop_();
+    // Volume commands wait here so a burst for one node collapses into its latest value
+    let volumes = RefCell::new(VolumeQueue::new(volume_stats.clone()));
     let timer = loop_.add_timer(move |_| {
         while let Ok(cmd) = cmd_receiver.try_recv() {
             match cmd {
                 PwCommand::SetVolume(id, vol, timestamp) => {
-                    let vol_pct = format!("{}%", (vol * 100.0) as u32);
-                    info!("EXEC: wpctl set-volume {} {}", id, vol_pct);
+                    volumes.borrow_mut().push(id, vol, timestamp, Instant::now());
+                }
+                PwCommand::SetMute(id, muted) => {
+                    let arg = if muted { "1" } else { "0" };
+                    info!("EXEC: wpctl set-mute {} {}", id, arg);
                     if let Ok(out) = Command::new("wpctl")
-                        .arg("set-volume")
+                        .arg("set-mute")
                         .arg(id.to_string())
-                        .arg(vol_pct)
+                        .arg(arg)
                         .output()
                     {
                         if !out.status.success() {
                             error!("wpctl error: {}", String::from_utf8_lossy(&out.stderr));
                         } else {
-                            // Signal volume change back to main loop for broadcasting
-                            let _ = sender_cmd.send(PwEvent::VolumeChanged(id, vol, timestamp));
+                            let _ = sender_cmd.send(PwEvent::MuteChanged(id, muted));
                         }
                     }
                 }
-                PwCommand::SetMute(id, muted) => {
-                    let arg = if muted { "1" } else { "0" };
-                    info!("EXEC: wpctl set-mute {} {}", id, arg);
-                    let _ = Command::new("wpctl")
-                        .arg("set-mute")
-                        .arg(id.to_string())
-                        .arg(arg)
-                        .spawn();
-                }
                 PwCommand::CreateLink(_out_node, out_port, _in_node, in_port) => {
                     info!("EXEC: pw-link {} {}", out_port, in_port);
                     let _ = Command::new("pw-link")
@@ -271,8 +497,64 @@ fn run_pipewire_loop(
                         }
                     }
                 }
+                PwCommand::MoveStream(node_id, target) => {
+                    info!("EXEC: pw-metadata {} target.object {}", node_id, target);
+                    if let Ok(out) = Command::new("pw-metadata")
+                        .arg(node_id.to_string())
+                        .arg("target.object")
+                        .arg(&target)
+                        .output()
+                    {
+                        if !out.status.success() {
+                            error!("pw-metadata error: {}", String::from_utf8_lossy(&out.stderr));
+                        }
+                    }
+                }
+                PwCommand::StartCapture(stream_id, request) => {
+                    info!("Starting capture stream {} on {}", stream_id, request.target_name);
+                    let name = format!("{}capture.{}", NODE_NAME_PREFIX, stream_id);
+                    match stream::create_capture(&core, &name, request) {
+                        Ok(active) => {
+                            streams.borrow_mut().insert(stream_id, active);
+                        }
+                        Err(e) => error!("Failed to create capture stream: {}", e),
+                    }
+                }
+                PwCommand::StartPlayback(stream_id, request) => {
+                    info!("Starting playback stream {} on {}", stream_id, request.target_name);
+                    let name = format!("{}playback.{}", NODE_NAME_PREFIX, stream_id);
+                    match stream::create_playback(&core, &name, request) {
+                        Ok(active) => {
+                            streams.borrow_mut().insert(stream_id, active);
+                        }
+                        Err(e) => error!("Failed to create playback stream: {}", e),
+                    }
+                }
+                PwCommand::StartVirtualSource(stream_id, request) => {
+                    info!("Creating virtual source {}: {}", stream_id, request.description);
+                    let name = format!("{}source.{}", NODE_NAME_PREFIX, stream_id);
+                    match stream::create_virtual_source(&core, &name, request) {
+                        Ok(active) => {
+                            streams.borrow_mut().insert(stream_id, active);
+                        }
+                        Err(e) => error!("Failed to create virtual source: {}", e),
+                    }
+                }
+                PwCommand::StopStream(stream_id) => {
+                    if streams.borrow_mut().remove(&stream_id).is_some() {
+                        info!("Stopped stream {}", stream_id);
+                    }
+                }
             }
         }
+
+        let due = volumes.borrow_mut().due(Instant::now());
+        for (id, vol, timestamp) in due {
+            apply_volume(id, vol, timestamp, &sender_cmd, &volume_stats);
+        }
+
+        // Drop streams whose consumer has gone away
+        streams.borrow_mut().retain(|_, s| !s.is_finished());
     });
 
     timer.update_timer(
@@ -0,0 +1,210 @@
+use crate::audio::generator::{Limited, PinkNoise, SampleSource, Samples, Sine, SpeakerSweep};
+use crate::audio::pipewire::PipeWireHandler;
+use crate::audio::stream::{PlaybackRequest, SAMPLE_RATE};
+use crate::models::playback::{PlaybackInfo, PlaybackSignal};
+use crate::utils::broadcast::{EventBroadcaster, ServerEvent};
+use crate::utils::files::{self, FileEntry};
+use anyhow::{bail, Context, Result};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+const SOUND_EXTENSIONS: &[&str] = &["wav"];
+
+pub struct PlayerConfig {
+    pub sounds_dir: PathBuf,
+    pub max_duration: Duration,
+}
+
+pub struct PlayTarget {
+    pub node_id: u32,
+    pub node_name: String,
+    pub channels: u32,
+}
+
+pub struct Player {
+    config: PlayerConfig,
+    active: Arc<Mutex<HashMap<u32, PlaybackInfo>>>,
+    broadcaster: Arc<EventBroadcaster>,
+    pw_handler: Arc<PipeWireHandler>,
+}
+
+impl Player {
+    pub fn new(
+        config: PlayerConfig,
+        broadcaster: Arc<EventBroadcaster>,
+        pw_handler: Arc<PipeWireHandler>,
+    ) -> Self {
+        Self {
+            config,
+            active: Arc::new(Mutex::new(HashMap::new())),
+            broadcaster,
+            pw_handler,
+        }
+    }
+
+    /// Starts playing `signal` into the target sink. Playback always ends
+    /// after `duration` (capped by the configured maximum), so a forgotten
+    /// test tone stops on its own.
+    pub fn start(
+        &self,
+        target: PlayTarget,
+        signal: PlaybackSignal,
+        level: f32,
+        duration: Option<Duration>,
+    ) -> Result<PlaybackInfo> {
+        if !(0.0..=1.0).contains(&level) {
+            bail!("Level must be between 0.0 and 1.0");
+        }
+        if !(1..=8).contains(&target.channels) {
+            bail!("Unsupported channel count: {}", target.channels);
+        }
+
+        let source: Box<dyn SampleSource> = match &signal {
+            PlaybackSignal::Sine { frequency } => {
+                if !(10.0..=(SAMPLE_RATE / 2) as f32).contains(frequency) {
+                    bail!("Frequency out of range: {}", frequency);
+                }
+                Box::new(Sine::new(target.channels, SAMPLE_RATE, *frequency, level))
+            }
+            PlaybackSignal::PinkNoise => Box::new(PinkNoise::new(target.channels, SAMPLE_RATE, level)),
+            PlaybackSignal::SpeakerSweep => {
+                Box::new(SpeakerSweep::new(target.channels, SAMPLE_RATE, level))
+            }
+            PlaybackSignal::File { name } => {
+                let path = self.sound_path(name).context("Sound file not found")?;
+                Box::new(Samples::from_wav(&path, level)?)
+            }
+        };
+
+        let timeout = duration
+            .unwrap_or(self.config.max_duration)
+            .min(self.config.max_duration);
+        let max_frames = timeout.as_millis() as u64 * source.rate() as u64 / 1000;
+
+        let id = self.pw_handler.allocate_stream_id();
+        let info = PlaybackInfo {
+            id,
+            node_id: target.node_id,
+            node_name: target.node_name.clone(),
+            signal,
+            level,
+            timeout_ms: timeout.as_millis() as u64,
+        };
+        // Registered before the stream exists so the tracker always finds it on teardown
+        self.active.lock().insert(id, info.clone());
+        info!("Playback {} started on {}", id, info.node_name);
+        self.broadcaster.send(ServerEvent::PlaybackStarted(info.clone()));
+
+        let tracked = Tracked {
+            inner: Limited::new(source, max_frames),
+            id,
+            reason: "stopped",
+            active: self.active.clone(),
+            broadcaster: self.broadcaster.clone(),
+        };
+        self.pw_handler.start_playback(id, PlaybackRequest {
+            target_id: target.node_id,
+            target_name: target.node_name,
+            source: Box::new(tracked),
+        });
+
+        Ok(info)
+    }
+
+    /// Returns false if no playback with this id is running.
+    pub fn stop(&self, id: u32) -> bool {
+        if !self.active.lock().contains_key(&id) {
+            return false;
+        }
+        self.pw_handler.stop_stream(id);
+        true
+    }
+
+    pub fn list_active(&self) -> Vec<PlaybackInfo> {
+        self.active.lock().values().cloned().collect()
+    }
+
+    pub fn list_sounds(&self) -> Result<Vec<FileEntry>> {
+        files::list(&self.config.sounds_dir, SOUND_EXTENSIONS)
+    }
+
+    pub fn sound_path(&self, name: &str) -> Option<PathBuf> {
+        files::resolve(&self.config.sounds_dir, name, SOUND_EXTENSIONS)
+    }
+
+    /// Stores an uploaded WAV file after checking that it decodes.
+    pub fn save_sound(&self, name: &str, data: &[u8]) -> Result<FileEntry> {
+        if !files::is_safe_name(name, SOUND_EXTENSIONS) {
+            bail!("Invalid file name: {}", name);
+        }
+        hound::WavReader::new(std::io::Cursor::new(data)).context("Not a valid WAV file")?;
+
+        fs::create_dir_all(&self.config.sounds_dir).with_context(|| {
+            format!("Failed to create sounds directory {}", self.config.sounds_dir.display())
+        })?;
+        let path = self.config.sounds_dir.join(name);
+        fs::write(&path, data)?;
+        info!("Saved sound {} ({} bytes)", name, data.len());
+
+        Ok(FileEntry {
+            name: name.to_string(),
+            size: data.len() as u64,
+            modified: files::unix_now(),
+        })
+    }
+
+    pub fn delete_sound(&self, name: &str) -> Result<bool> {
+        match self.sound_path(name) {
+            Some(path) => {
+                fs::remove_file(&path)?;
+                info!("Deleted sound {}", name);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+/// Wraps a playing source and reports the end of playback when the stream
+/// that owns it is torn down, whatever the cause.
+struct Tracked {
+    inner: Limited<Box<dyn SampleSource>>,
+    id: u32,
+    reason: &'static str,
+    active: Arc<Mutex<HashMap<u32, PlaybackInfo>>>,
+    broadcaster: Arc<EventBroadcaster>,
+}
+
+impl SampleSource for Tracked {
+    fn channels(&self) -> u32 {
+        self.inner.channels()
+    }
+
+    fn rate(&self) -> u32 {
+        self.inner.rate()
+    }
+
+    fn fill(&mut self, out: &mut [f32]) -> usize {
+        let n = self.inner.fill(out);
+        if n == 0 {
+            self.reason = if self.inner.is_exhausted() { "timeout" } else { "finished" };
+        }
+        n
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.active.lock().remove(&self.id);
+        info!("Playback {} ended ({})", self.id, self.reason);
+        self.broadcaster.send(ServerEvent::PlaybackStopped {
+            id: self.id,
+            reason: self.reason.to_string(),
+        });
+    }
+}
@@ -0,0 +1,286 @@
+use crate::audio::pipewire::PipeWireHandler;
+use crate::audio::stream::{CaptureRequest, SAMPLE_RATE};
+use crate::models::recording::{RecordingFormat, RecordingInfo};
+use crate::utils::broadcast::{EventBroadcaster, ServerEvent};
+use crate::utils::files::{self, FileEntry};
+use anyhow::{bail, Context, Result};
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+const BITS_PER_SAMPLE: u16 = 24;
+const WAV_HEADER_BYTES: u64 = 44;
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+const RECORDING_EXTENSIONS: &[&str] = &["wav", "flac"];
+
+pub struct RecorderConfig {
+    pub dir: PathBuf,
+    pub max_duration: Duration,
+    /// Limit on the WAV data written while recording; a FLAC recording is
+    /// encoded from that file afterwards and only comes out smaller.
+    pub max_bytes: u64,
+}
+
+pub struct RecordTarget {
+    pub node_id: u32,
+    pub node_name: String,
+    pub is_sink: bool,
+}
+
+pub struct Recorder {
+    config: RecorderConfig,
+    active: Arc<Mutex<HashMap<u32, RecordingInfo>>>,
+    broadcaster: Arc<EventBroadcaster>,
+    pw_handler: Arc<PipeWireHandler>,
+}
+
+impl Recorder {
+    pub fn new(
+        config: RecorderConfig,
+        broadcaster: Arc<EventBroadcaster>,
+        pw_handler: Arc<PipeWireHandler>,
+    ) -> Self {
+        Self {
+            config,
+            active: Arc::new(Mutex::new(HashMap::new())),
+            broadcaster,
+            pw_handler,
+        }
+    }
+
+    pub fn start(
+        &self,
+        target: RecordTarget,
+        format: RecordingFormat,
+        channels: u32,
+    ) -> Result<RecordingInfo> {
+        if !(1..=8).contains(&channels) {
+            bail!("Unsupported channel count: {}", channels);
+        }
+        fs::create_dir_all(&self.config.dir).with_context(|| {
+            format!("Failed to create recordings directory {}", self.config.dir.display())
+        })?;
+
+        // The stream id keeps recordings of one node started within a second apart
+        let id = self.pw_handler.allocate_stream_id();
+        let stem = format!("{}-{}-{}", files::sanitize(&target.node_name), files::unix_now(), id);
+        let part_path = self.config.dir.join(format!("{}.wav.part", stem));
+        let spec = hound::WavSpec {
+            channels: channels as u16,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: BITS_PER_SAMPLE,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let writer = hound::WavWriter::create(&part_path, spec)
+            .with_context(|| format!("Failed to create {}", part_path.display()))?;
+
+        let (tx, rx) = crossbeam_channel::bounded(64);
+        self.pw_handler.start_capture(id, CaptureRequest {
+            target_id: target.node_id,
+            target_name: target.node_name.clone(),
+            capture_sink: target.is_sink,
+            channels,
+            sender: tx,
+        });
+
+        let info = RecordingInfo {
+            id,
+            node_id: target.node_id,
+            node_name: target.node_name,
+            file: format!("{}.{}", stem, format.extension()),
+            format,
+            channels,
+            sample_rate: SAMPLE_RATE,
+            elapsed_ms: 0,
+            bytes: WAV_HEADER_BYTES,
+        };
+        self.active.lock().insert(id, info.clone());
+        self.broadcaster.send(ServerEvent::RecordingStarted(info.clone()));
+        info!("Recording {} started: {}", id, info.file);
+
+        let session = WriterSession {
+            id,
+            part_path,
+            dir: self.config.dir.clone(),
+            stem,
+            format,
+            channels,
+            max_frames: self.config.max_duration.as_secs() * SAMPLE_RATE as u64,
+            max_bytes: self.config.max_bytes,
+            active: self.active.clone(),
+            broadcaster: self.broadcaster.clone(),
+            pw_handler: self.pw_handler.clone(),
+        };
+        thread::spawn(move || session.run(writer, rx));
+
+        Ok(info)
+    }
+
+    /// Returns false if no recording with this id is running.
+    pub fn stop(&self, id: u32) -> bool {
+        if !self.active.lock().contains_key(&id) {
+            return false;
+        }
+        // Dropping the capture stream closes the channel, which lets the writer finalize
+        self.pw_handler.stop_stream(id);
+        true
+    }
+
+    pub fn list_active(&self) -> Vec<RecordingInfo> {
+        self.active.lock().values().cloned().collect()
+    }
+
+    pub fn list_files(&self) -> Result<Vec<FileEntry>> {
+        files::list(&self.config.dir, RECORDING_EXTENSIONS)
+    }
+
+    pub fn file_path(&self, name: &str) -> Option<PathBuf> {
+        files::resolve(&self.config.dir, name, RECORDING_EXTENSIONS)
+    }
+
+    pub fn delete_file(&self, name: &str) -> Result<bool> {
+        match self.file_path(name) {
+            Some(path) => {
+                fs::remove_file(&path)?;
+                info!("Deleted recording {}", name);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+type WavFileWriter = hound::WavWriter<std::io::BufWriter<fs::File>>;
+
+struct WriterSession {
+    id: u32,
+    part_path: PathBuf,
+    dir: PathBuf,
+    stem: String,
+    format: RecordingFormat,
+    channels: u32,
+    max_frames: u64,
+    max_bytes: u64,
+    active: Arc<Mutex<HashMap<u32, RecordingInfo>>>,
+    broadcaster: Arc<EventBroadcaster>,
+    pw_handler: Arc<PipeWireHandler>,
+}
+
+impl WriterSession {
+    fn run(self, mut writer: WavFileWriter, rx: Receiver<Vec<f32>>) {
+        let bytes_per_frame = self.channels as u64 * (BITS_PER_SAMPLE / 8) as u64;
+        let scale = ((1i32 << (BITS_PER_SAMPLE - 1)) - 1) as f32;
+        let mut samples_written: u64 = 0;
+        let mut last_progress = Instant::now();
+
+        let reason = loop {
+            match rx.recv_timeout(PROGRESS_INTERVAL) {
+                Ok(chunk) => {
+                    let written = chunk
+                        .into_iter()
+                        .try_for_each(|s| writer.write_sample((s.clamp(-1.0, 1.0) * scale) as i32));
+                    if let Err(e) = written {
+                        error!("Recording {} write error: {}", self.id, e);
+                        break "write_error";
+                    }
+                    samples_written = writer.len() as u64;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break "stopped",
+            }
+
+            let frames = samples_written / self.channels as u64;
+            let bytes = WAV_HEADER_BYTES + frames * bytes_per_frame;
+            if frames >= self.max_frames {
+                break "max_duration";
+            }
+            if bytes >= self.max_bytes {
+                break "max_size";
+            }
+            if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                last_progress = Instant::now();
+                self.report_progress(frames, bytes);
+            }
+        };
+
+        // Release the stream if we stopped on our own
+        drop(rx);
+        self.pw_handler.stop_stream(self.id);
+
+        let id = self.id;
+        let file = match self.finish(writer) {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Recording {} failed to finalize: {}", id, e);
+                String::new()
+            }
+        };
+
+        self.active.lock().remove(&id);
+        info!("Recording {} finished ({}): {}", id, reason, file);
+        self.broadcaster.send(ServerEvent::RecordingStopped {
+            id,
+            file,
+            reason: reason.to_string(),
+        });
+    }
+
+    fn report_progress(&self, frames: u64, bytes: u64) {
+        let elapsed_ms = frames * 1000 / SAMPLE_RATE as u64;
+        if let Some(info) = self.active.lock().get_mut(&self.id) {
+            info.elapsed_ms = elapsed_ms;
+            info.bytes = bytes;
+        }
+        self.broadcaster.send(ServerEvent::RecordingProgress {
+            id: self.id,
+            elapsed_ms,
+            bytes,
+        });
+    }
+
+    fn finish(&self, writer: WavFileWriter) -> Result<String> {
+        writer.finalize()?;
+
+        let wav_path = self.dir.join(format!("{}.wav", self.stem));
+        fs::rename(&self.part_path, &wav_path)?;
+
+        if self.format == RecordingFormat::Wav {
+            return Ok(file_name(&wav_path));
+        }
+
+        let flac_path = self.dir.join(format!("{}.flac", self.stem));
+        info!("EXEC: flac {}", wav_path.display());
+        let out = Command::new("flac")
+            .arg("--silent")
+            .arg("--force")
+            .arg("--delete-input-file")
+            .arg("-o")
+            .arg(&flac_path)
+            .arg(&wav_path)
+            .output();
+        match out {
+            Ok(out) if out.status.success() => Ok(file_name(&flac_path)),
+            Ok(out) => {
+                warn!("flac error, keeping WAV: {}", String::from_utf8_lossy(&out.stderr));
+                Ok(file_name(&wav_path))
+            }
+            Err(e) => {
+                warn!("Could not run flac, keeping WAV: {}", e);
+                Ok(file_name(&wav_path))
+            }
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
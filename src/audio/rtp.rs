@@ -0,0 +1,232 @@
+use crate::models::rtp::{RtpConfig, RtpDirection, RtpInstance};
+use crate::utils::broadcast::{EventBroadcaster, ServerEvent};
+use anyhow::{bail, Context, Result};
+use parking_lot::Mutex;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+// pw-cli fails fast on bad arguments; anything still running after this is loaded
+const STARTUP_GRACE: Duration = Duration::from_millis(300);
+const RATES: &[u32] = &[44100, 48000, 88200, 96000];
+
+/// Start of RTP node names. Kept apart from `NODE_NAME_PREFIX` on purpose:
+/// senders and receivers are routed like any device, so presets and rules
+/// must not take them for the server's own streams.
+const RTP_NODE_PREFIX: &str = "pipewire-web-remote-rtp-";
+
+struct Loaded {
+    info: RtpInstance,
+    child: Child,
+}
+
+/// Runs PipeWire RTP sink/source modules. Each instance is loaded by its own
+/// `pw-cli -m load-module` process, which keeps the module alive until it is
+/// killed.
+pub struct RtpManager {
+    instances: Mutex<HashMap<u32, Loaded>>,
+    next_id: AtomicU32,
+    broadcaster: Arc<EventBroadcaster>,
+}
+
+impl RtpManager {
+    pub fn new(broadcaster: Arc<EventBroadcaster>) -> Self {
+        Self {
+            instances: Mutex::new(HashMap::new()),
+            next_id: AtomicU32::new(1),
+            broadcaster,
+        }
+    }
+
+    pub fn load(&self, config: RtpConfig) -> Result<RtpInstance> {
+        if config.port == 0 {
+            bail!("Port must not be 0");
+        }
+        if !RATES.contains(&config.rate) {
+            bail!("Unsupported sample rate: {}", config.rate);
+        }
+        if !(1..=8).contains(&config.channels) {
+            bail!("Unsupported channel count: {}", config.channels);
+        }
+        if !(1..=2000).contains(&config.latency_ms) {
+            bail!("Latency must be between 1 and 2000 ms");
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (module, node_name, args) = module_args(id, &config);
+        let args_str = args.to_string();
+
+        info!("EXEC: pw-cli -m load-module {} {}", module, args_str);
+        let mut child = Command::new("pw-cli")
+            .arg("-m")
+            .arg("load-module")
+            .arg(module)
+            .arg(&args_str)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to run pw-cli")?;
+
+        thread::sleep(STARTUP_GRACE);
+        if let Some(status) = child.try_wait()? {
+            let mut stderr = String::new();
+            if let Some(mut pipe) = child.stderr.take() {
+                let _ = pipe.read_to_string(&mut stderr);
+            }
+            bail!("pw-cli exited with {}: {}", status, stderr.trim());
+        }
+        if let Some(pipe) = child.stderr.take() {
+            thread::spawn(move || {
+                for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+                    warn!("rtp {}: {}", id, line);
+                }
+            });
+        }
+
+        let info = RtpInstance {
+            id,
+            config,
+            node_name,
+            node_id: None,
+            properties: BTreeMap::new(),
+            pid: child.id(),
+            module_args: args,
+        };
+        self.instances.lock().insert(id, Loaded {
+            info: info.clone(),
+            child,
+        });
+        info!("RTP {} loaded ({})", id, info.node_name);
+        self.broadcaster.send(ServerEvent::RtpLoaded(info.clone()));
+
+        Ok(info)
+    }
+
+    /// Returns false if no instance with this id is loaded.
+    pub fn unload(&self, id: u32) -> bool {
+        let Some(loaded) = self.instances.lock().remove(&id) else {
+            return false;
+        };
+        self.finish(loaded, "unloaded");
+        true
+    }
+
+    pub fn unload_all(&self) {
+        let all: Vec<Loaded> = self.instances.lock().drain().map(|(_, l)| l).collect();
+        for loaded in all {
+            self.finish(loaded, "shutdown");
+        }
+    }
+
+    /// Lists running instances, dropping any whose pw-cli process has died.
+    pub fn list(&self) -> Vec<RtpInstance> {
+        let mut instances = self.instances.lock();
+        let exited: Vec<u32> = instances
+            .iter_mut()
+            .filter_map(|(id, l)| (!matches!(l.child.try_wait(), Ok(None))).then_some(*id))
+            .collect();
+        for id in exited {
+            warn!("RTP {} exited unexpectedly", id);
+            instances.remove(&id);
+            self.broadcaster.send(ServerEvent::RtpUnloaded {
+                id,
+                reason: "exited".to_string(),
+            });
+        }
+
+        let mut list: Vec<RtpInstance> = instances.values().map(|l| l.info.clone()).collect();
+        list.sort_by_key(|i| i.id);
+        list
+    }
+
+    fn finish(&self, mut loaded: Loaded, reason: &str) {
+        let id = loaded.info.id;
+        if let Err(e) = loaded.child.kill() {
+            error!("Failed to stop RTP {}: {}", id, e);
+        }
+        let _ = loaded.child.wait();
+        info!("RTP {} unloaded ({})", id, reason);
+        self.broadcaster.send(ServerEvent::RtpUnloaded {
+            id,
+            reason: reason.to_string(),
+        });
+    }
+}
+
+fn module_args(id: u32, config: &RtpConfig) -> (&'static str, String, serde_json::Value) {
+    let endpoint = format!("{}:{}", config.address, config.port);
+    let (module, kind, class, address_key, port_key) = match config.direction {
+        RtpDirection::Sink => (
+            "libpipewire-module-rtp-sink",
+            "sink",
+            "Audio/Sink",
+            "destination.ip",
+            "destination.port",
+        ),
+        RtpDirection::Source => (
+            "libpipewire-module-rtp-source",
+            "source",
+            "Audio/Source",
+            "source.ip",
+            "source.port",
+        ),
+    };
+    let node_name = format!("{}{}.{}", RTP_NODE_PREFIX, kind, id);
+    let description = config
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("RTP {} {}", kind, endpoint));
+
+    let mut args = json!({
+        address_key: config.address.to_string(),
+        port_key: config.port,
+        "sess.name": description,
+        "sess.latency.msec": config.latency_ms,
+        "audio.format": config.format.as_spa(),
+        "audio.rate": config.rate,
+        "audio.channels": config.channels,
+        "stream.props": {
+            "node.name": node_name,
+            "node.description": description,
+            "media.class": class,
+        },
+    });
+    // Lets a sender and receiver on the same machine share a multicast group
+    if config.address.is_multicast() {
+        args["net.loop"] = json!(true);
+    }
+
+    (module, node_name, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::pipewire::NODE_NAME_PREFIX;
+
+    #[test]
+    fn nodes_are_not_own_streams() {
+        for direction in [RtpDirection::Sink, RtpDirection::Source] {
+            let config = RtpConfig {
+                direction,
+                address: "127.0.0.1".parse().unwrap(),
+                port: 46000,
+                format: Default::default(),
+                rate: 48000,
+                channels: 2,
+                latency_ms: 100,
+                name: None,
+            };
+            let (_, node_name, args) = module_args(3, &config);
+            assert!(!node_name.starts_with(NODE_NAME_PREFIX), "{}", node_name);
+            assert_eq!(args["stream.props"]["node.name"], node_name.as_str());
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use crate::audio::generator::SampleSource;
+use crossbeam_channel::{Sender, TrySendError};
+use pipewire as pw;
+use pw::core::Core;
+use pw::properties::properties;
+use pw::spa;
+use pw::stream::{Stream, StreamFlags};
+use spa::param::audio::{AudioFormat, AudioInfoRaw};
+use spa::pod::Pod;
+use std::any::Any;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// All server-side streams run in F32LE at this rate; PipeWire converts as needed
+pub const SAMPLE_RATE: u32 = 48000;
+
+pub struct CaptureRequest {
+    pub target_id: u32,
+    pub target_name: String,
+    pub capture_sink: bool, // capture the monitor of a sink instead of its input
+    pub channels: u32,
+    pub sender: Sender<Vec<f32>>,
+}
+
+pub struct PlaybackRequest {
+    pub target_id: u32,
+    pub target_name: String,
+    pub source: Box<dyn SampleSource>,
+}
+
+pub struct VirtualSourceRequest {
+    pub description: String,
+    pub source: Box<dyn SampleSource>,
+}
+
+pub struct ActiveStream {
+    // Listener must be dropped before the stream it is registered on
+    _listener: Box<dyn Any>,
+    _stream: Stream,
+    finished: Arc<AtomicBool>,
+}
+
+impl ActiveStream {
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+}
+
+fn channel_positions(channels: u32) -> [u32; 64] {
+    use spa::sys::*;
+
+    let layout: &[u32] = match channels {
+        1 => &[SPA_AUDIO_CHANNEL_MONO],
+        2 => &[SPA_AUDIO_CHANNEL_FL, SPA_AUDIO_CHANNEL_FR],
+        6 => &[
+            SPA_AUDIO_CHANNEL_FL,
+            SPA_AUDIO_CHANNEL_FR,
+            SPA_AUDIO_CHANNEL_FC,
+            SPA_AUDIO_CHANNEL_LFE,
+            SPA_AUDIO_CHANNEL_RL,
+            SPA_AUDIO_CHANNEL_RR,
+        ],
+        8 => &[
+            SPA_AUDIO_CHANNEL_FL,
+            SPA_AUDIO_CHANNEL_FR,
+            SPA_AUDIO_CHANNEL_FC,
+            SPA_AUDIO_CHANNEL_LFE,
+            SPA_AUDIO_CHANNEL_RL,
+            SPA_AUDIO_CHANNEL_RR,
+            SPA_AUDIO_CHANNEL_SL,
+            SPA_AUDIO_CHANNEL_SR,
+        ],
+        _ => &[],
+    };
+
+    let mut position = [0u32; 64];
+    position[..layout.len()].copy_from_slice(layout);
+    position
+}
+
+fn format_param(rate: u32, channels: u32) -> Vec<u8> {
+    let mut info = AudioInfoRaw::new();
+    info.set_format(AudioFormat::F32LE);
+    info.set_rate(rate);
+    info.set_channels(channels);
+    info.set_position(channel_positions(channels));
+
+    let obj = spa::pod::Object {
+        type_: spa::utils::SpaTypes::ObjectParamFormat.as_raw(),
+        id: spa::param::ParamType::EnumFormat.as_raw(),
+        properties: info.into(),
+    };
+    spa::pod::serialize::PodSerializer::serialize(
+        std::io::Cursor::new(Vec::new()),
+        &spa::pod::Value::Object(obj),
+    )
+    .expect("Failed to serialize audio format")
+    .0
+    .into_inner()
+}
+
+/// Opens an input stream on `req.target_id` and forwards interleaved f32
+/// frames to `req.sender`. The stream flags itself finished once the
+/// receiving side hangs up, so the owner can drop it on the next sweep.
+pub fn create_capture(core: &Core, name: &str, req: CaptureRequest) -> anyhow::Result<ActiveStream> {
+    let mut props = properties! {
+        *pw::keys::MEDIA_TYPE => "Audio",
+        *pw::keys::MEDIA_CATEGORY => "Capture",
+        *pw::keys::MEDIA_ROLE => "Production",
+        *pw::keys::NODE_NAME => name,
+        "target.object" => req.target_name.as_str(),
+    };
+    if req.capture_sink {
+        props.insert(*pw::keys::STREAM_CAPTURE_SINK, "true");
+    }
+
+    let stream = Stream::new(core, name, props)?;
+    let finished = Arc::new(AtomicBool::new(false));
+    let finished_cb = finished.clone();
+    let sender = req.sender;
+
+    let listener = stream
+        .add_local_listener_with_user_data(())
+        .process(move |stream, _| {
+            let Some(mut buffer) = stream.dequeue_buffer() else {
+                return;
+            };
+            let datas = buffer.datas_mut();
+            if datas.is_empty() {
+                return;
+            }
+            let data = &mut datas[0];
+            let offset = data.chunk().offset() as usize;
+            let size = data.chunk().size() as usize;
+            let Some(bytes) = data.data() else {
+                return;
+            };
+            let end = (offset + size).min(bytes.len());
+            let samples: Vec<f32> = bytes[offset.min(end)..end]
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+
+            // Never block the loop; a full queue just drops this quantum
+            if let Err(TrySendError::Disconnected(_)) = sender.try_send(samples) {
+                finished_cb.store(true, Ordering::Relaxed);
+            }
+        })
+        .register()?;
+
+    let values = format_param(SAMPLE_RATE, req.channels);
+    let mut params = [Pod::from_bytes(&values).expect("Invalid format pod")];
+    stream.connect(
+        spa::utils::Direction::Input,
+        Some(req.target_id),
+        StreamFlags::AUTOCONNECT | StreamFlags::MAP_BUFFERS,
+        &mut params,
+    )?;
+
+    Ok(ActiveStream {
+        _listener: Box::new(listener),
+        _stream: stream,
+        finished,
+    })
+}
+
+/// Opens an output stream into `req.target_id` that plays `req.source` at its
+/// own rate and channel count. The stream flags itself finished once the
+/// source runs dry.
+pub fn create_playback(core: &Core, name: &str, req: PlaybackRequest) -> anyhow::Result<ActiveStream> {
+    let props = properties! {
+        *pw::keys::MEDIA_TYPE => "Audio",
+        *pw::keys::MEDIA_CATEGORY => "Playback",
+        *pw::keys::MEDIA_ROLE => "Test",
+        *pw::keys::NODE_NAME => name,
+        "target.object" => req.target_name.as_str(),
+    };
+
+    create_output(
+        core,
+        name,
+        props,
+        req.source,
+        Some(req.target_id),
+        StreamFlags::AUTOCONNECT | StreamFlags::MAP_BUFFERS,
+    )
+}
+
+/// Creates an `Audio/Source` node fed by `req.source` that other clients can
+/// record from. It is left unlinked; the session manager or the user routes it.
+pub fn create_virtual_source(core: &Core, name: &str, req: VirtualSourceRequest) -> anyhow::Result<ActiveStream> {
+    let props = properties! {
+        *pw::keys::MEDIA_TYPE => "Audio",
+        *pw::keys::MEDIA_CLASS => "Audio/Source",
+        *pw::keys::NODE_NAME => name,
+        *pw::keys::NODE_DESCRIPTION => req.description.as_str(),
+    };
+
+    create_output(core, name, props, req.source, None, StreamFlags::MAP_BUFFERS)
+}
+
+fn create_output(
+    core: &Core,
+    name: &str,
+    props: pw::properties::Properties,
+    mut source: Box<dyn SampleSource>,
+    target_id: Option<u32>,
+    flags: StreamFlags,
+) -> anyhow::Result<ActiveStream> {
+    let stream = Stream::new(core, name, props)?;
+    let finished = Arc::new(AtomicBool::new(false));
+    let finished_cb = finished.clone();
+    let channels = source.channels();
+    let rate = source.rate();
+    let mut scratch: Vec<f32> = Vec::new();
+
+    let listener = stream
+        .add_local_listener_with_user_data(())
+        .process(move |stream, _| {
+            let Some(mut buffer) = stream.dequeue_buffer() else {
+                return;
+            };
+            let datas = buffer.datas_mut();
+            if datas.is_empty() {
+                return;
+            }
+            let data = &mut datas[0];
+            let stride = 4 * channels as usize;
+            let n_frames = match data.data() {
+                Some(bytes) => {
+                    let max_frames = bytes.len() / stride;
+                    scratch.resize(max_frames * channels as usize, 0.0);
+                    let n = source.fill(&mut scratch);
+                    if n == 0 {
+                        finished_cb.store(true, Ordering::Relaxed);
+                    }
+                    for (out, s) in bytes.chunks_exact_mut(4).zip(&scratch[..n * channels as usize]) {
+                        out.copy_from_slice(&s.to_le_bytes());
+                    }
+                    n
+                }
+                None => 0,
+            };
+            let chunk = data.chunk_mut();
+            *chunk.offset_mut() = 0;
+            *chunk.stride_mut() = stride as i32;
+            *chunk.size_mut() = (stride * n_frames) as u32;
+        })
+        .register()?;
+
+    let values = format_param(rate, channels);
+    let mut params = [Pod::from_bytes(&values).expect("Invalid format pod")];
+    stream.connect(spa::utils::Direction::Output, target_id, flags, &mut params)?;
+
+    Ok(ActiveStream {
+        _listener: Box::new(listener),
+        _stream: stream,
+        finished,
+    })
+}
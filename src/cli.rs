@@ -1,4 +1,5 @@
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(
@@ -23,6 +24,51 @@ pub struct Cli {
     )]
     pub allow_external: bool,
 
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Directory for recordings [default: <music dir>/pipewire-web-remote]"
+    )]
+    pub recordings_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 3600,
+        help = "Maximum length of a single recording"
+    )]
+    pub max_recording_secs: u64,
+
+    #[arg(
+        long,
+        value_name = "MB",
+        default_value_t = 2048,
+        help = "Maximum size of a single recording, counted as uncompressed WAV"
+    )]
+    pub max_recording_mb: u64,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Directory for uploaded sounds [default: <data dir>/pipewire-web-remote/sounds]"
+    )]
+    pub sounds_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 30,
+        help = "Maximum length of test tone or sound playback"
+    )]
+    pub max_playback_secs: u64,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Directory for saved presets and settings [default: <config dir>/pipewire-web-remote]"
+    )]
+    pub config_dir: Option<PathBuf>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -62,4 +108,29 @@ impl Cli {
     pub fn is_external(&self) -> bool {
         self.allow_external || self.listen.starts_with("0.0.0.0:")
     }
+
+    pub fn get_recordings_dir(&self) -> PathBuf {
+        self.recordings_dir.clone().unwrap_or_else(|| {
+            dirs::audio_dir()
+                .or_else(dirs::data_dir)
+                .unwrap_or_else(std::env::temp_dir)
+                .join("pipewire-web-remote")
+        })
+    }
+
+    pub fn get_sounds_dir(&self) -> PathBuf {
+        self.sounds_dir.clone().unwrap_or_else(|| {
+            dirs::data_dir()
+                .unwrap_or_else(std::env::temp_dir)
+                .join("pipewire-web-remote/sounds")
+        })
+    }
+
+    pub fn get_config_dir(&self) -> PathBuf {
+        self.config_dir.clone().unwrap_or_else(|| {
+            dirs::config_dir()
+                .unwrap_or_else(std::env::temp_dir)
+                .join("pipewire-web-remote")
+        })
+    }
 }
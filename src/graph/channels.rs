@@ -0,0 +1,240 @@
+use crate::graph::manager::GraphManager;
+use crate::graph::validate::{self, LinkError, FORMAT_KEY};
+use crate::models::graph::{Link, NodeConnection, Port, PortDirection, PortPair};
+
+/// Plans links between every audio output of `output_node` and the inputs
+/// of `input_node`, pairing ports by channel. Each pair goes through
+/// `validate::check_link`; pairs that are already linked are reported as
+/// existing rather than refused.
+pub fn plan_connect(
+    graph: &GraphManager,
+    output_node: u32,
+    input_node: u32,
+    allow_cycle: bool,
+) -> Result<NodeConnection, LinkError> {
+    for node in [output_node, input_node] {
+        if graph.get_node(node).is_none() {
+            return Err(LinkError::NodeNotFound { node });
+        }
+    }
+    let outputs = audio_ports(graph, output_node, PortDirection::Output);
+    let inputs = audio_ports(graph, input_node, PortDirection::Input);
+
+    let pairs = pair(&outputs, &inputs);
+    let mut connection = NodeConnection {
+        unpaired: outputs
+            .iter()
+            .filter(|o| !pairs.iter().any(|(p, _)| p.id == o.id))
+            .map(|o| o.id)
+            .collect(),
+        ..Default::default()
+    };
+    for (output, input) in pairs {
+        let port_pair = PortPair {
+            output_port: output.id,
+            input_port: input.id,
+            output_channel: output.channel.clone(),
+            input_channel: input.channel.clone(),
+        };
+        match validate::check_link(graph, output_node, output.id, input_node, input.id, allow_cycle) {
+            Ok(()) => connection.created.push(port_pair),
+            Err(LinkError::LinkExists { .. }) => connection.existing.push(port_pair),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(connection)
+}
+
+/// Every link running from `output_node` into `input_node`.
+pub fn links_between(graph: &GraphManager, output_node: u32, input_node: u32) -> Vec<Link> {
+    let mut links: Vec<Link> = graph
+        .links_of_node(output_node)
+        .filter(|l| l.output_node == output_node && l.input_node == input_node)
+        .cloned()
+        .collect();
+    links.sort_by_key(|l| l.id);
+    links
+}
+
+fn audio_ports(graph: &GraphManager, node_id: u32, direction: PortDirection) -> Vec<&Port> {
+    graph
+        .ports_of(node_id)
+        .filter(|p| p.direction == direction)
+        .filter(|p| p.properties.get(FORMAT_KEY).is_none_or(|f| validate::media(f) == "audio"))
+        .collect()
+}
+
+/// Pairs ports the way a mixer would:
+/// - one output fans out to the front pair (or every input if there is none),
+/// - several outputs into one input downmix,
+/// - otherwise equal channels are paired (FL→FL, FR→FR) and surround
+///   channels the inputs lack fold onto the front pair (5.1 into stereo),
+///   falling back to port order when the two sides share no channel names
+///   (e.g. `AUX0`).
+pub fn pair<'a>(outputs: &[&'a Port], inputs: &[&'a Port]) -> Vec<(&'a Port, &'a Port)> {
+    let find = |channel: &str| inputs.iter().copied().find(|p| p.channel.as_deref() == Some(channel));
+    match (outputs, inputs) {
+        ([], _) | (_, []) => Vec::new(),
+        ([output], _) => {
+            let front: Vec<&Port> = ["FL", "FR"].iter().filter_map(|c| find(c)).collect();
+            let targets = if front.len() == 2 { front } else { inputs.to_vec() };
+            targets.into_iter().map(|input| (*output, input)).collect()
+        }
+        (_, [input]) => outputs.iter().map(|output| (*output, *input)).collect(),
+        _ => {
+            let mut pairs = Vec::new();
+            let mut matched = false;
+            for output in outputs {
+                let Some(channel) = output.channel.as_deref().filter(|c| *c != "UNK") else {
+                    continue;
+                };
+                if let Some(input) = find(channel) {
+                    matched = true;
+                    pairs.push((*output, input));
+                } else {
+                    pairs.extend(fold(channel).iter().filter_map(|c| find(c)).map(|input| (*output, input)));
+                }
+            }
+            if matched {
+                pairs
+            } else {
+                outputs.iter().copied().zip(inputs.iter().copied()).collect()
+            }
+        }
+    }
+}
+
+/// Front channels a surround channel is mixed into when the other side
+/// doesn't have it: centre and LFE go to both, side and rear to their side.
+fn fold(channel: &str) -> &'static [&'static str] {
+    match channel {
+        "FC" | "LFE" | "LFE2" | "RC" | "TC" | "TFC" | "TRC" | "BC" | "MONO" => &["FL", "FR"],
+        "SL" | "RL" | "RLC" | "FLC" | "TFL" | "TRL" | "TSL" | "FLW" | "FLH" => &["FL"],
+        "SR" | "RR" | "RRC" | "FRC" | "TFR" | "TRR" | "TSR" | "FRW" | "FRH" => &["FR"],
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::graph::{Node, NodeGroups, NodeType};
+    use std::collections::BTreeMap;
+
+    fn ports(node_id: u32, direction: PortDirection, channels: &[&str]) -> Vec<Port> {
+        let base = node_id * 100 + if direction == PortDirection::Output { 0 } else { 50 };
+        channels
+            .iter()
+            .zip(base..)
+            .map(|(channel, id)| Port {
+                id,
+                node_id,
+                name: format!("port_{}", channel),
+                direction,
+                channel: Some(channel.to_string()),
+                properties: BTreeMap::new(),
+            })
+            .collect()
+    }
+
+    /// `pair` over the given channels, as (output channel, input channel).
+    fn paired(outputs: &[&str], inputs: &[&str]) -> Vec<(String, String)> {
+        let outputs = ports(1, PortDirection::Output, outputs);
+        let inputs = ports(2, PortDirection::Input, inputs);
+        let outputs: Vec<&Port> = outputs.iter().collect();
+        let inputs: Vec<&Port> = inputs.iter().collect();
+        pair(&outputs, &inputs)
+            .into_iter()
+            .map(|(o, i)| (o.channel.clone().unwrap(), i.channel.clone().unwrap()))
+            .collect()
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected.iter().map(|(o, i)| (o.to_string(), i.to_string())).collect()
+    }
+
+    #[test]
+    fn stereo_to_stereo() {
+        assert_eq!(paired(&["FR", "FL"], &["FL", "FR"]), pairs(&[("FR", "FR"), ("FL", "FL")]));
+    }
+
+    #[test]
+    fn mono_fans_out_to_the_front_pair() {
+        assert_eq!(paired(&["MONO"], &["FL", "FR"]), pairs(&[("MONO", "FL"), ("MONO", "FR")]));
+        assert_eq!(
+            paired(&["MONO"], &["FL", "FR", "FC", "LFE"]),
+            pairs(&[("MONO", "FL"), ("MONO", "FR")])
+        );
+        assert_eq!(paired(&["MONO"], &["AUX0", "AUX1"]), pairs(&[("MONO", "AUX0"), ("MONO", "AUX1")]));
+    }
+
+    #[test]
+    fn stereo_downmixes_to_mono() {
+        assert_eq!(paired(&["FL", "FR"], &["MONO"]), pairs(&[("FL", "MONO"), ("FR", "MONO")]));
+    }
+
+    #[test]
+    fn surround_folds_onto_stereo() {
+        assert_eq!(
+            paired(&["FL", "FR", "FC", "LFE", "RL", "RR"], &["FL", "FR"]),
+            pairs(&[
+                ("FL", "FL"),
+                ("FR", "FR"),
+                ("FC", "FL"),
+                ("FC", "FR"),
+                ("LFE", "FL"),
+                ("LFE", "FR"),
+                ("RL", "FL"),
+                ("RR", "FR"),
+            ])
+        );
+        // Channels the inputs have are still paired one to one
+        assert_eq!(
+            paired(&["FL", "FR", "FC", "LFE", "RL", "RR"], &["FL", "FR", "RL", "RR"]),
+            pairs(&[
+                ("FL", "FL"),
+                ("FR", "FR"),
+                ("FC", "FL"),
+                ("FC", "FR"),
+                ("LFE", "FL"),
+                ("LFE", "FR"),
+                ("RL", "RL"),
+                ("RR", "RR"),
+            ])
+        );
+    }
+
+    #[test]
+    fn unknown_channels_pair_in_order() {
+        assert_eq!(paired(&["UNK", "UNK"], &["FL", "FR"]), pairs(&[("UNK", "FL"), ("UNK", "FR")]));
+        assert_eq!(
+            paired(&["AUX0", "AUX1", "AUX2"], &["AUX5", "AUX6"]),
+            pairs(&[("AUX0", "AUX5"), ("AUX1", "AUX6")])
+        );
+    }
+
+    #[test]
+    fn plan_reports_unpaired_outputs() {
+        let mut graph = GraphManager::new();
+        for id in [1, 2] {
+            graph.add_node(Node {
+                id,
+                name: format!("node{}", id),
+                node_type: NodeType::Device,
+                ports: Vec::new(),
+                properties: BTreeMap::new(),
+                groups: NodeGroups::default(),
+            });
+        }
+        for port in ports(1, PortDirection::Output, &["FL", "FR", "AUX0"]) {
+            graph.add_port(port);
+        }
+        for port in ports(2, PortDirection::Input, &["FL", "FR"]) {
+            graph.add_port(port);
+        }
+        let connection = plan_connect(&graph, 1, 2, false).unwrap();
+        let created: Vec<(u32, u32)> = connection.created.iter().map(|p| (p.output_port, p.input_port)).collect();
+        assert_eq!(created, [(100, 250), (101, 251)]);
+        assert_eq!(connection.unpaired, [102]);
+    }
+}
@@ -0,0 +1,328 @@
+use crate::graph::layout;
+use crate::graph::qpwgraph::escape;
+use crate::graph::validate::{self, FORMAT_KEY};
+use crate::models::graph::{AudioGraph, Node, Port, PortDirection};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+const NODE_WIDTH: f32 = 200.0;
+const COLUMN_GAP: f32 = 120.0;
+const ROW_GAP: f32 = 24.0;
+const HEADER_HEIGHT: f32 = 24.0;
+const PORT_HEIGHT: f32 = 16.0;
+const MARGIN: f32 = 20.0;
+
+/// Renders the graph as Graphviz DOT: one record per node with its inputs
+/// on the left and outputs on the right, links as edges between ports.
+pub fn dot(graph: &AudioGraph) -> String {
+    let mut out = String::from("digraph pipewire {\n");
+    out.push_str("    rankdir=LR;\n");
+    out.push_str("    node [shape=record, style=\"rounded,filled\", fontname=\"sans-serif\", fontsize=10];\n");
+    out.push_str("    edge [penwidth=1.5];\n");
+
+    for node in &graph.nodes {
+        let mut fields = Vec::new();
+        for direction in [PortDirection::Input, PortDirection::Output] {
+            let ports: Vec<String> = node
+                .ports
+                .iter()
+                .filter(|p| p.direction == direction)
+                .map(|p| format!("<p{}> {}", p.id, record_escape(port_label(p))))
+                .collect();
+            if !ports.is_empty() {
+                fields.push(format!("{{{}}}", ports.join("|")));
+            }
+            if direction == PortDirection::Input {
+                fields.push(record_escape(title(node)));
+            }
+        }
+        let _ = writeln!(
+            out,
+            "    n{} [label=\"{{{}}}\", fillcolor=\"{}\", tooltip=\"{} ({})\"];",
+            node.id,
+            fields.join("|"),
+            kind_color(node),
+            dot_escape(&node.name),
+            node.id,
+        );
+    }
+
+    // Links to nodes the graph doesn't track would show up as bare nodes
+    let ports = port_index(graph);
+    for link in &graph.links {
+        let (Some(output), Some(_)) = (ports.get(&link.output_port), ports.get(&link.input_port)) else {
+            continue;
+        };
+        let _ = writeln!(
+            out,
+            "    n{}:p{}:e -> n{}:p{}:w [color=\"{}\"];",
+            link.output_node, link.output_port, link.input_node, link.input_port, port_color(output),
+        );
+    }
+    out.push_str("}\n");
+    out
+}
+
+struct Placed<'a> {
+    node: &'a Node,
+    x: f32,
+    y: f32,
+    height: f32,
+}
+
+/// Renders the graph as a standalone SVG using the layered layout, so no
+/// Graphviz install is needed.
+pub fn svg(graph: &AudioGraph) -> String {
+    let nodes: HashMap<u32, &Node> = graph.nodes.iter().map(|n| (n.id, n)).collect();
+    let mut placed: HashMap<u32, Placed> = HashMap::new();
+    let mut width = MARGIN;
+    let mut height = MARGIN;
+    for (column, ids) in layout::layers(graph).iter().enumerate() {
+        let x = MARGIN + column as f32 * (NODE_WIDTH + COLUMN_GAP);
+        let mut y = MARGIN;
+        for id in ids {
+            let Some(node) = nodes.get(id) else { continue };
+            let node_height = node_height(node);
+            placed.insert(*id, Placed { node, x, y, height: node_height });
+            y += node_height + ROW_GAP;
+        }
+        width = width.max(x + NODE_WIDTH + MARGIN);
+        height = height.max(y - ROW_GAP + MARGIN);
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\" font-size=\"11\">",
+        w = width,
+        h = height,
+    );
+    let _ = writeln!(out, "<rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>");
+
+    // Links first, so nodes are drawn over their ends
+    let ports = port_index(graph);
+    for link in &graph.links {
+        let (Some(from), Some(to)) = (
+            placed.get(&link.output_node).and_then(|p| port_anchor(p, link.output_port)),
+            placed.get(&link.input_node).and_then(|p| port_anchor(p, link.input_port)),
+        ) else {
+            continue;
+        };
+        let bend = ((to.0 - from.0).abs() / 2.0).max(40.0);
+        let color = ports.get(&link.output_port).map_or(media_color("audio"), |p| port_color(p));
+        let _ = writeln!(
+            out,
+            "<path d=\"M{} {} C{} {} {} {} {} {}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\"/>",
+            from.0, from.1, from.0 + bend, from.1, to.0 - bend, to.1, to.0, to.1, color,
+        );
+    }
+
+    let mut order: Vec<&Placed> = placed.values().collect();
+    order.sort_by_key(|p| p.node.id);
+    for p in order {
+        let _ = writeln!(out, "<g><title>{} ({})</title>", escape(&p.node.name), p.node.id);
+        let _ = writeln!(
+            out,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"6\" fill=\"{}\" stroke=\"#555555\"/>",
+            p.x, p.y, NODE_WIDTH, p.height, kind_color(p.node),
+        );
+        let _ = writeln!(
+            out,
+            "<text x=\"{}\" y=\"{}\" font-weight=\"bold\">{}</text>",
+            p.x + 8.0, p.y + 16.0, escape(&truncate(title(p.node), 28)),
+        );
+        for (port, row, anchor, x) in port_rows(p) {
+            let _ = writeln!(
+                out,
+                "<circle cx=\"{}\" cy=\"{}\" r=\"3\" fill=\"{}\"/>",
+                x, row, port_color(port),
+            );
+            let (text_x, text_anchor) = match anchor {
+                PortDirection::Input => (x + 8.0, "start"),
+                PortDirection::Output => (x - 8.0, "end"),
+            };
+            let _ = writeln!(
+                out,
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"{}\" font-size=\"10\">{}</text>",
+                text_x, row + 3.5, text_anchor, escape(&truncate(port_label(port), 12)),
+            );
+        }
+        out.push_str("</g>\n");
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+fn node_height(node: &Node) -> f32 {
+    let inputs = node.ports.iter().filter(|p| p.direction == PortDirection::Input).count();
+    let outputs = node.ports.len() - inputs;
+    HEADER_HEIGHT + inputs.max(outputs).max(1) as f32 * PORT_HEIGHT + 6.0
+}
+
+/// Each port with its row's y, its side and the x of its anchor.
+fn port_rows<'a>(p: &Placed<'a>) -> Vec<(&'a Port, f32, PortDirection, f32)> {
+    let mut rows = Vec::new();
+    for (direction, x) in [(PortDirection::Input, p.x), (PortDirection::Output, p.x + NODE_WIDTH)] {
+        let ports = p.node.ports.iter().filter(|port| port.direction == direction);
+        for (i, port) in ports.enumerate() {
+            let y = p.y + HEADER_HEIGHT + (i as f32 + 0.5) * PORT_HEIGHT;
+            rows.push((port, y, direction, x));
+        }
+    }
+    rows
+}
+
+fn port_anchor(p: &Placed, port_id: u32) -> Option<(f32, f32)> {
+    port_rows(p)
+        .into_iter()
+        .find(|(port, ..)| port.id == port_id)
+        .map(|(_, y, _, x)| (x, y))
+}
+
+fn port_index(graph: &AudioGraph) -> HashMap<u32, &Port> {
+    graph
+        .nodes
+        .iter()
+        .flat_map(|n| &n.ports)
+        .map(|p| (p.id, p))
+        .collect()
+}
+
+fn title(node: &Node) -> &str {
+    node.properties
+        .get("node.description")
+        .or_else(|| node.properties.get("node.nick"))
+        .unwrap_or(&node.name)
+}
+
+fn port_label(port: &Port) -> &str {
+    port.channel.as_deref().filter(|c| *c != "UNK").unwrap_or(&port.name)
+}
+
+fn truncate(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        return s.to_string();
+    }
+    let mut short: String = s.chars().take(max - 1).collect();
+    short.push('…');
+    short
+}
+
+/// Fill color by node kind: hardware and virtual sinks, sources, playback
+/// and capture streams.
+fn kind_color(node: &Node) -> &'static str {
+    match node.properties.get("media.class").map(String::as_str) {
+        Some(c) if c.starts_with("Audio/Sink") => "#d6eaf8",
+        Some(c) if c.starts_with("Audio/Source") => "#d5f5e3",
+        Some(c) if c.starts_with("Stream/Output") => "#fdebd0",
+        Some(c) if c.starts_with("Stream/Input") => "#ebdef0",
+        _ => "#eeeeee",
+    }
+}
+
+fn port_color(port: &Port) -> &'static str {
+    media_color(port.properties.get(FORMAT_KEY).map_or("audio", |f| validate::media(f)))
+}
+
+fn media_color(media: &str) -> &'static str {
+    match media {
+        "audio" => "#2e86c1",
+        "midi" => "#c0392b",
+        "video" => "#8e44ad",
+        _ => "#7f8c8d",
+    }
+}
+
+/// Escapes a string for a double-quoted DOT attribute.
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Escapes the characters that structure a record label.
+fn record_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '{' | '}' | '|' | '<' | '>' | '"') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::graph::{Link, NodeGroups, NodeType};
+    use std::collections::BTreeMap;
+
+    fn node(id: u32, name: &str, description: &str, ports: Vec<Port>) -> Node {
+        Node {
+            id,
+            name: name.to_string(),
+            node_type: NodeType::Device,
+            ports,
+            properties: BTreeMap::from([("node.description".to_string(), description.to_string())]),
+            groups: NodeGroups::default(),
+        }
+    }
+
+    fn port(id: u32, node_id: u32, name: &str, direction: PortDirection) -> Port {
+        Port {
+            id,
+            node_id,
+            name: name.to_string(),
+            direction,
+            channel: None,
+            properties: BTreeMap::new(),
+        }
+    }
+
+    fn link(id: u32, output: (u32, u32), input: (u32, u32)) -> Link {
+        Link {
+            id,
+            output_node: output.0,
+            output_port: output.1,
+            input_node: input.0,
+            input_port: input.1,
+        }
+    }
+
+    /// A player linked to a mixer with awkward names, plus a link between
+    /// MIDI nodes 50 and 60 that the graph doesn't track.
+    fn graph() -> AudioGraph {
+        AudioGraph {
+            nodes: vec![
+                node(1, "player", "R&D <synth>", vec![port(10, 1, "out|L", PortDirection::Output)]),
+                node(2, "mixer \"main\"", "Mixer {A|B}", vec![port(20, 2, "<in>", PortDirection::Input)]),
+            ],
+            links: vec![link(100, (1, 10), (2, 20)), link(200, (50, 51), (60, 61))],
+        }
+    }
+
+    #[test]
+    fn dot_skips_links_between_untracked_nodes() {
+        let dot = dot(&graph());
+        let edges: Vec<&str> = dot.lines().filter(|l| l.contains("->")).collect();
+        assert_eq!(edges, ["    n1:p10:e -> n2:p20:w [color=\"#2e86c1\"];"]);
+        assert!(!dot.contains("n50") && !dot.contains("n60"));
+    }
+
+    #[test]
+    fn dot_escapes_labels() {
+        let dot = dot(&graph());
+        assert!(dot.contains(r#"label="{R&D \<synth\>|{<p10> out\|L}}""#), "{}", dot);
+        assert!(dot.contains(r#"label="{{<p20> \<in\>}|Mixer \{A\|B\}}""#), "{}", dot);
+        assert!(dot.contains(r#"tooltip="mixer \"main\" (2)""#), "{}", dot);
+    }
+
+    #[test]
+    fn svg_skips_untracked_links_and_escapes_text() {
+        let svg = svg(&graph());
+        assert_eq!(svg.matches("<path").count(), 1);
+        assert!(svg.contains("R&amp;D &lt;synth&gt;"));
+        assert!(svg.contains("<title>mixer &quot;main&quot; (2)</title>"));
+        assert!(svg.contains("&lt;in&gt;"));
+        assert!(!svg.contains("<in>") && !svg.contains("<synth>"));
+    }
+}
@@ -0,0 +1,215 @@
+use crate::models::graph::{AudioGraph, GroupBy, Node, NodeGroup, NodeGroups, Port};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// Reads a node's client, device and application from its properties.
+pub fn node_groups(properties: &BTreeMap<String, String>) -> NodeGroups {
+    let id = |key: &str| properties.get(key).and_then(|v| v.parse().ok());
+    NodeGroups {
+        client: id("client.id"),
+        device: id("device.id"),
+        application: properties
+            .get("application.name")
+            .or_else(|| properties.get("application.process.binary"))
+            .cloned(),
+    }
+}
+
+fn by_name(by: GroupBy) -> &'static str {
+    match by {
+        GroupBy::Client => "client",
+        GroupBy::Device => "device",
+        GroupBy::Application => "application",
+    }
+}
+
+fn key_of(node: &Node, by: GroupBy) -> Option<String> {
+    match by {
+        GroupBy::Client => node.groups.client.map(|id| id.to_string()),
+        GroupBy::Device => node.groups.device.map(|id| id.to_string()),
+        GroupBy::Application => node.groups.application.clone(),
+    }
+}
+
+fn label_of(node: &Node, by: GroupBy, key: &str) -> String {
+    let property = |keys: &[&str]| keys.iter().find_map(|k| node.properties.get(*k)).cloned();
+    match by {
+        GroupBy::Client => property(&["application.name", "application.process.binary"])
+            .unwrap_or_else(|| format!("Client {}", key)),
+        GroupBy::Device => property(&["alsa.card_name", "api.alsa.card.name", "device.description", "node.nick"])
+            .unwrap_or_else(|| format!("Device {}", key)),
+        GroupBy::Application => key.to_string(),
+    }
+}
+
+/// Every group of the given kind, ordered by key. Nodes without a parent of
+/// that kind are left out.
+pub fn groups(graph: &AudioGraph, by: GroupBy) -> Vec<NodeGroup> {
+    let mut groups: BTreeMap<String, NodeGroup> = BTreeMap::new();
+    for node in &graph.nodes {
+        let Some(key) = key_of(node, by) else { continue };
+        groups
+            .entry(key.clone())
+            .or_insert_with(|| NodeGroup {
+                by,
+                label: label_of(node, by, &key),
+                key,
+                nodes: Vec::new(),
+            })
+            .nodes
+            .push(node.id);
+    }
+    groups.into_values().collect()
+}
+
+/// The graph with each group of two or more nodes merged into one aggregate
+/// node, or only the groups named in `only`. An aggregate takes its lowest
+/// member's id and carries all member ports, so links keep their real port
+/// ids; ports and links are moved onto the aggregate's id, and links inside
+/// a group are dropped.
+pub fn collapse(graph: &AudioGraph, by: GroupBy, only: Option<&HashSet<String>>) -> AudioGraph {
+    let mut aggregate: HashMap<u32, u32> = HashMap::new();
+    let mut merged: BTreeMap<u32, Node> = BTreeMap::new();
+    for group in groups(graph, by) {
+        if group.nodes.len() < 2 || only.is_some_and(|only| !only.contains(&group.key)) {
+            continue;
+        }
+        let members: BTreeSet<u32> = group.nodes.iter().copied().collect();
+        let members: Vec<&Node> = graph.nodes.iter().filter(|n| members.contains(&n.id)).collect();
+        let first = members[0];
+        let mut properties = BTreeMap::from([
+            ("group.by".to_string(), by_name(by).to_string()),
+            ("group.key".to_string(), group.key.clone()),
+            ("group.members".to_string(), group.nodes.iter().map(u32::to_string).collect::<Vec<_>>().join(",")),
+        ]);
+        if let Some(class) = first.properties.get("media.class") {
+            properties.insert("media.class".to_string(), class.clone());
+        }
+        for member in &members {
+            aggregate.insert(member.id, first.id);
+        }
+        merged.insert(first.id, Node {
+            id: first.id,
+            name: group.label,
+            node_type: first.node_type.clone(),
+            ports: members
+                .iter()
+                .flat_map(|m| m.ports.iter())
+                .map(|p| Port { node_id: first.id, ..p.clone() })
+                .collect(),
+            properties,
+            groups: first.groups.clone(),
+        });
+    }
+
+    let mut nodes: Vec<Node> = graph
+        .nodes
+        .iter()
+        .filter(|n| !aggregate.contains_key(&n.id))
+        .cloned()
+        .chain(merged.into_values())
+        .collect();
+    nodes.sort_by_key(|n| n.id);
+
+    let links = graph
+        .links
+        .iter()
+        .map(|l| {
+            let mut link = l.clone();
+            link.output_node = aggregate.get(&l.output_node).copied().unwrap_or(l.output_node);
+            link.input_node = aggregate.get(&l.input_node).copied().unwrap_or(l.input_node);
+            link
+        })
+        .filter(|l| l.output_node != l.input_node || !aggregate.contains_key(&l.output_node))
+        .collect();
+
+    AudioGraph { nodes, links }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::graph::{Link, NodeType, PortDirection};
+
+    fn node(id: u32, device: Option<&str>, ports: Vec<Port>) -> Node {
+        let mut properties = BTreeMap::from([("media.class".to_string(), "Audio/Sink".to_string())]);
+        if let Some(device) = device {
+            properties.insert("device.id".to_string(), device.to_string());
+        }
+        Node {
+            id,
+            name: format!("node{}", id),
+            node_type: NodeType::Device,
+            ports,
+            groups: node_groups(&properties),
+            properties,
+        }
+    }
+
+    fn port(id: u32, node_id: u32, direction: PortDirection) -> Port {
+        Port {
+            id,
+            node_id,
+            name: format!("port{}", id),
+            direction,
+            channel: None,
+            properties: BTreeMap::new(),
+        }
+    }
+
+    fn link(id: u32, output: (u32, u32), input: (u32, u32)) -> Link {
+        Link {
+            id,
+            output_node: output.0,
+            output_port: output.1,
+            input_node: input.0,
+            input_port: input.1,
+        }
+    }
+
+    /// A player (1) feeding two profiles (2, 3) of one card, and a link
+    /// between the profiles.
+    fn graph() -> AudioGraph {
+        AudioGraph {
+            nodes: vec![
+                node(1, None, vec![port(10, 1, PortDirection::Output)]),
+                node(2, Some("40"), vec![port(20, 2, PortDirection::Input), port(21, 2, PortDirection::Output)]),
+                node(3, Some("40"), vec![port(30, 3, PortDirection::Input)]),
+            ],
+            links: vec![
+                link(100, (1, 10), (2, 20)),
+                link(101, (1, 10), (3, 30)),
+                link(102, (2, 21), (3, 30)),
+            ],
+        }
+    }
+
+    #[test]
+    fn collapse_moves_ports_and_links_onto_the_aggregate() {
+        let collapsed = collapse(&graph(), GroupBy::Device, None);
+        let ids: Vec<u32> = collapsed.nodes.iter().map(|n| n.id).collect();
+        assert_eq!(ids, [1, 2]);
+
+        let aggregate = &collapsed.nodes[1];
+        assert_eq!(aggregate.properties["group.members"], "2,3");
+        let ports: Vec<(u32, u32)> = aggregate.ports.iter().map(|p| (p.id, p.node_id)).collect();
+        assert_eq!(ports, [(20, 2), (21, 2), (30, 2)]);
+
+        // Every link end names a node and a port that node carries
+        for link in &collapsed.links {
+            for (node_id, port_id) in [(link.output_node, link.output_port), (link.input_node, link.input_port)] {
+                let node = collapsed.nodes.iter().find(|n| n.id == node_id).unwrap();
+                assert!(node.ports.iter().any(|p| p.id == port_id && p.node_id == node_id));
+            }
+        }
+        let links: Vec<u32> = collapsed.links.iter().map(|l| l.id).collect();
+        assert_eq!(links, [100, 101]);
+    }
+
+    #[test]
+    fn collapse_only_named_groups() {
+        let only = HashSet::from(["41".to_string()]);
+        let collapsed = collapse(&graph(), GroupBy::Device, Some(&only));
+        assert_eq!(collapsed.nodes.len(), 3);
+        assert_eq!(collapsed.links.len(), 3);
+    }
+}
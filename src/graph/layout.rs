@@ -0,0 +1,175 @@
+use crate::models::graph::{AudioGraph, Node};
+use crate::models::layout::Position;
+use crate::utils::files;
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+// Node sizes as the patchbay draws them
+const NODE_BASE_HEIGHT: f32 = 50.0;
+const PORT_HEIGHT: f32 = 36.0;
+const COLUMN_WIDTH: f32 = 320.0;
+const NODE_GAP: f32 = 20.0;
+const MARGIN: f32 = 50.0;
+
+/// Patchbay node positions shared by every client, kept by node name in a
+/// JSON file that is rewritten on every change.
+pub struct LayoutStore {
+    path: PathBuf,
+    positions: Mutex<BTreeMap<String, Position>>,
+}
+
+impl LayoutStore {
+    /// Loads positions from `path`; a missing file is an empty layout.
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let positions = files::read_json(&path)?;
+        Ok(Self {
+            path,
+            positions: Mutex::new(positions),
+        })
+    }
+
+    pub fn positions(&self) -> BTreeMap<String, Position> {
+        self.positions.lock().clone()
+    }
+
+    /// Sets the given positions, clearing those mapped to `None`, and
+    /// returns the entries that actually changed.
+    pub fn update(&self, changes: BTreeMap<String, Option<Position>>) -> Result<BTreeMap<String, Option<Position>>> {
+        let mut positions = self.positions.lock();
+        let changed: BTreeMap<String, Option<Position>> = changes
+            .into_iter()
+            .filter(|(name, position)| positions.get(name) != position.as_ref())
+            .collect();
+        if changed.is_empty() {
+            return Ok(changed);
+        }
+        let old = positions.clone();
+        for (name, position) in &changed {
+            match position {
+                Some(position) => positions.insert(name.clone(), *position),
+                None => positions.remove(name),
+            };
+        }
+        if let Err(e) = files::write_json(&self.path, &*positions) {
+            *positions = old;
+            return Err(e);
+        }
+        Ok(changed)
+    }
+}
+
+/// Layered positions for the graph's nodes that have none saved. Each
+/// column starts below the saved nodes already sitting in it.
+pub fn auto_positions(graph: &AudioGraph, saved: &BTreeMap<String, Position>) -> BTreeMap<String, Position> {
+    let nodes: BTreeMap<u32, &Node> = graph.nodes.iter().map(|n| (n.id, n)).collect();
+    let mut auto = BTreeMap::new();
+    for (column, ids) in layers(graph).iter().enumerate() {
+        let x = MARGIN + column as f32 * COLUMN_WIDTH;
+        let mut y = graph
+            .nodes
+            .iter()
+            .filter_map(|n| saved.get(&n.name).map(|p| (n, p)))
+            .filter(|(_, p)| (p.x - x).abs() < COLUMN_WIDTH / 2.0)
+            .map(|(n, p)| p.y + node_height(n) + NODE_GAP)
+            .fold(MARGIN, f32::max);
+        for node in ids.iter().filter_map(|id| nodes.get(id)) {
+            if saved.contains_key(&node.name) || auto.contains_key(&node.name) {
+                continue;
+            }
+            auto.insert(node.name.clone(), Position { x, y });
+            y += node_height(node) + NODE_GAP;
+        }
+    }
+    auto
+}
+
+fn node_height(node: &Node) -> f32 {
+    NODE_BASE_HEIGHT + node.ports.len() as f32 * PORT_HEIGHT
+}
+
+/// Splits the graph into columns, sources left and sinks right. A node sits
+/// one column after the furthest node feeding it; nodes that only consume
+/// audio move to the last column. Within a column nodes follow the average
+/// row of what feeds them, which keeps most links from crossing.
+pub fn layers(graph: &AudioGraph) -> Vec<Vec<u32>> {
+    let mut upstream: BTreeMap<u32, BTreeSet<u32>> = graph.nodes.iter().map(|n| (n.id, BTreeSet::new())).collect();
+    let mut downstream: BTreeMap<u32, BTreeSet<u32>> = upstream.clone();
+    for link in &graph.links {
+        if link.output_node == link.input_node
+            || !upstream.contains_key(&link.output_node)
+            || !upstream.contains_key(&link.input_node)
+        {
+            continue;
+        }
+        upstream.entry(link.input_node).or_default().insert(link.output_node);
+        downstream.entry(link.output_node).or_default().insert(link.input_node);
+    }
+
+    // Longest path from the sources; a loop is broken at its lowest id
+    let mut layer: BTreeMap<u32, usize> = BTreeMap::new();
+    let mut waiting: BTreeMap<u32, usize> = upstream.iter().map(|(&id, ups)| (id, ups.len())).collect();
+    while !waiting.is_empty() {
+        let mut ready: Vec<u32> = waiting.iter().filter(|(_, &n)| n == 0).map(|(&id, _)| id).collect();
+        if ready.is_empty() {
+            ready.extend(waiting.keys().next());
+        }
+        for id in ready {
+            waiting.remove(&id);
+            let column = upstream[&id]
+                .iter()
+                .filter_map(|up| layer.get(up))
+                .map(|l| l + 1)
+                .max()
+                .unwrap_or(0);
+            layer.insert(id, column);
+            for down in &downstream[&id] {
+                if let Some(n) = waiting.get_mut(down) {
+                    *n = n.saturating_sub(1);
+                }
+            }
+        }
+    }
+
+    let last = layer.values().copied().max().unwrap_or(0).max(1);
+    for node in &graph.nodes {
+        let consumes_only = if upstream[&node.id].is_empty() && downstream[&node.id].is_empty() {
+            is_consumer(node)
+        } else {
+            downstream[&node.id].is_empty()
+        };
+        if consumes_only {
+            layer.insert(node.id, last);
+        }
+    }
+
+    let mut columns: Vec<Vec<u32>> = vec![Vec::new(); last + 1];
+    for (&id, &column) in &layer {
+        columns[column].push(id);
+    }
+    let mut row: BTreeMap<u32, f32> = BTreeMap::new();
+    for column in &mut columns {
+        let key = |id: &u32| {
+            let rows: Vec<f32> = upstream[id].iter().filter_map(|up| row.get(up)).copied().collect();
+            if rows.is_empty() {
+                f32::MAX
+            } else {
+                rows.iter().sum::<f32>() / rows.len() as f32
+            }
+        };
+        column.sort_by(|a, b| key(a).total_cmp(&key(b)).then(a.cmp(b)));
+        for (i, id) in column.iter().enumerate() {
+            row.insert(*id, i as f32);
+        }
+    }
+    columns.retain(|c| !c.is_empty());
+    columns
+}
+
+/// Sinks and capture streams, which belong on the right even unlinked.
+fn is_consumer(node: &Node) -> bool {
+    node.properties
+        .get("media.class")
+        .is_some_and(|c| c.starts_with("Audio/Sink") || c.starts_with("Stream/Input"))
+}
@@ -1,89 +1,700 @@
-use crate::models::graph::{AudioGraph, Link, Node, Port};
-use std::collections::HashMap;
+use crate::models::graph::{AudioGraph, Flow, HardwareRoute, Link, Node, Port, PortDirection, Routes};
+use parking_lot::Mutex;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 
+/// Routes listed by `routes` before giving up; fan-out through a few mixers
+/// multiplies quickly.
+const MAX_ROUTES: usize = 256;
+
+/// Node property shared by the capture and playback halves of a loopback or
+/// filter-chain, which pass audio internally rather than over a link.
+const LINK_GROUP_KEY: &str = "node.link-group";
+
+/// Graph state indexed by id. Nodes are stored without their ports; the
+/// `AudioGraph` served to clients is assembled on demand and cached until the
+/// next change.
 #[derive(Debug)]
 pub struct GraphManager {
-    graph: AudioGraph,
-    orphan_ports: HashMap<u32, Vec<Port>>,
+    nodes: HashMap<u32, Node>,
+    ports: HashMap<u32, Port>,
+    links: HashMap<u32, Link>,
+    // Ports per node in arrival order; may refer to a node not seen yet
+    node_ports: HashMap<u32, Vec<u32>>,
+    port_links: HashMap<u32, HashSet<u32>>,
+    node_links: HashMap<u32, HashSet<u32>>,
+    link_groups: HashMap<String, BTreeSet<u32>>,
+    snapshot: Mutex<Option<Arc<AudioGraph>>>,
+    revision: u64,
 }
 
 impl GraphManager {
     pub fn new() -> Self {
         Self {
-            graph: AudioGraph {
-                nodes: Vec::new(),
-                links: Vec::new(),
-            },
-            orphan_ports: HashMap::new(),
+            nodes: HashMap::new(),
+            ports: HashMap::new(),
+            links: HashMap::new(),
+            node_ports: HashMap::new(),
+            port_links: HashMap::new(),
+            node_links: HashMap::new(),
+            link_groups: HashMap::new(),
+            snapshot: Mutex::new(None),
+            revision: 0,
         }
     }
 
-    pub fn get_graph(&self) -> &AudioGraph {
-        &self.graph
+    /// Serializable view of the whole graph, with nodes ordered by id and
+    /// ports in the order they appeared. Shared until the graph changes.
+    pub fn get_graph(&self) -> Arc<AudioGraph> {
+        self.snapshot
+            .lock()
+            .get_or_insert_with(|| Arc::new(self.build_graph()))
+            .clone()
     }
 
-    pub fn clear(&mut self) {
-        self.graph.nodes.clear();
-        self.graph.links.clear();
-        self.orphan_ports.clear();
+    fn build_graph(&self) -> AudioGraph {
+        let mut nodes: Vec<Node> = self
+            .nodes
+            .values()
+            .map(|n| Node {
+                ports: self.ports_of(n.id).cloned().collect(),
+                ..n.clone()
+            })
+            .collect();
+        nodes.sort_by_key(|n| n.id);
+
+        let mut links: Vec<Link> = self.links.values().cloned().collect();
+        links.sort_by_key(|l| l.id);
+
+        AudioGraph { nodes, links }
     }
 
-    pub fn add_node(&mut self, mut node: Node) {
-        self.graph.nodes.retain(|n| n.id != node.id);
+    /// Revision of the last device or graph event applied.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn set_revision(&mut self, revision: u64) {
+        self.revision = revision;
+    }
+
+    fn invalidate(&mut self) {
+        *self.snapshot.get_mut() = None;
+    }
+
+    pub fn get_node(&self, id: u32) -> Option<&Node> {
+        self.nodes.get(&id)
+    }
+
+    pub fn get_port(&self, id: u32) -> Option<&Port> {
+        self.ports.get(&id)
+    }
+
+    pub fn get_link(&self, id: u32) -> Option<&Link> {
+        self.links.get(&id)
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.nodes.values()
+    }
+
+    pub fn links(&self) -> impl Iterator<Item = &Link> {
+        self.links.values()
+    }
+
+    pub fn ports_of(&self, node_id: u32) -> impl Iterator<Item = &Port> {
+        self.node_ports
+            .get(&node_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.ports.get(id))
+    }
+
+    /// Looks a port up by node and port name. If several nodes share the
+    /// name, the lowest id with a matching port wins.
+    pub fn find_port(&self, node_name: &str, port_name: &str, direction: PortDirection) -> Option<&Port> {
+        let mut nodes: Vec<&Node> = self.nodes.values().filter(|n| n.name == node_name).collect();
+        nodes.sort_by_key(|n| n.id);
+        nodes.into_iter().find_map(|n| {
+            self.ports_of(n.id)
+                .find(|p| p.name == port_name && p.direction == direction)
+        })
+    }
+
+    pub fn links_of_port(&self, port_id: u32) -> impl Iterator<Item = &Link> {
+        self.port_links
+            .get(&port_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.links.get(id))
+    }
+
+    pub fn links_of_node(&self, node_id: u32) -> impl Iterator<Item = &Link> {
+        self.node_links
+            .get(&node_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.links.get(id))
+    }
+
+    /// Nodes one link away in the direction of `flow`, in id order. The
+    /// receiving half of a loopback or filter-chain counts as linked to its
+    /// sending half.
+    pub fn neighbours(&self, node_id: u32, flow: Flow) -> BTreeSet<u32> {
+        let mut neighbours: BTreeSet<u32> = self
+            .links_of_node(node_id)
+            .filter_map(|l| match flow {
+                Flow::Downstream if l.output_node == node_id => Some(l.input_node),
+                Flow::Upstream if l.input_node == node_id => Some(l.output_node),
+                _ => None,
+            })
+            .collect();
+        neighbours.extend(self.link_group_peers(node_id, flow));
+        neighbours
+    }
+
+    /// The other halves of `node_id`'s link group that audio passes to
+    /// (downstream) or comes from (upstream).
+    fn link_group_peers(&self, node_id: u32, flow: Flow) -> impl Iterator<Item = u32> + '_ {
+        let node = self.nodes.get(&node_id);
+        let (from, to) = match flow {
+            Flow::Downstream => (Side::Receiving, Side::Sending),
+            Flow::Upstream => (Side::Sending, Side::Receiving),
+        };
+        node.filter(|n| side(n) == Some(from))
+            .and_then(|n| n.properties.get(LINK_GROUP_KEY))
+            .and_then(|group| self.link_groups.get(group))
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(move |&id| id != node_id && self.nodes.get(&id).and_then(side) == Some(to))
+    }
+
+    /// Breadth-first walk from `from`, mapping each reached node to the node
+    /// it was reached through.
+    fn walk(&self, from: u32, flow: Flow) -> HashMap<u32, u32> {
+        let mut previous = HashMap::from([(from, from)]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(node) = queue.pop_front() {
+            for next in self.neighbours(node, flow) {
+                if let Entry::Vacant(entry) = previous.entry(next) {
+                    entry.insert(node);
+                    queue.push_back(next);
+                }
+            }
+        }
+        previous
+    }
+
+    fn trace(previous: &HashMap<u32, u32>, from: u32, to: u32) -> Option<Vec<u32>> {
+        let mut path = vec![to];
+        let mut current = to;
+        while current != from {
+            current = *previous.get(&current)?;
+            path.push(current);
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Shortest chain of nodes from `from` to `to` following links
+    /// downstream, both ends included.
+    pub fn find_path(&self, from: u32, to: u32) -> Option<Vec<u32>> {
+        Self::trace(&self.walk(from, Flow::Downstream), from, to)
+    }
+
+    /// Every node reachable from `node_id` and the routes leading there,
+    /// through filters, loopbacks and virtual sinks alike; the two halves of
+    /// a loopback appear as consecutive nodes. A route ends where
+    /// nothing is linked further or where it would loop.
+    pub fn routes(&self, node_id: u32, flow: Flow) -> Routes {
+        let mut nodes: Vec<u32> = self.walk(node_id, flow).into_keys().filter(|&n| n != node_id).collect();
+        nodes.sort_unstable();
 
-        // Attach orphans if any
-        if let Some(ports) = self.orphan_ports.remove(&node.id) {
-            for p in ports {
-                if !node.ports.iter().any(|np| np.id == p.id) {
-                    node.ports.push(p);
+        let mut paths = Vec::new();
+        let mut truncated = false;
+        let mut stack = vec![vec![node_id]];
+        while let Some(path) = stack.pop() {
+            let last = path[path.len() - 1];
+            let next: Vec<u32> = self
+                .neighbours(last, flow)
+                .into_iter()
+                .filter(|n| !path.contains(n))
+                .collect();
+            if next.is_empty() {
+                if path.len() > 1 {
+                    if paths.len() == MAX_ROUTES {
+                        truncated = true;
+                        break;
+                    }
+                    paths.push(path);
                 }
+                continue;
+            }
+            // Reversed so routes come out in id order
+            for n in next.into_iter().rev() {
+                let mut longer = path.clone();
+                longer.push(n);
+                stack.push(longer);
             }
         }
 
-        self.graph.nodes.push(node);
+        Routes {
+            node: node_id,
+            flow,
+            nodes,
+            paths,
+            truncated,
+        }
+    }
+
+    /// Hardware sinks that `node_id`'s audio ultimately reaches, each with
+    /// the shortest route there.
+    pub fn hardware_outputs(&self, node_id: u32) -> Vec<HardwareRoute> {
+        let previous = self.walk(node_id, Flow::Downstream);
+        let mut outputs: Vec<HardwareRoute> = previous
+            .keys()
+            .filter(|&&id| id != node_id)
+            .filter_map(|&id| self.nodes.get(&id))
+            .filter(|n| is_hardware_sink(n))
+            .filter_map(|n| {
+                Some(HardwareRoute {
+                    node: n.id,
+                    name: n.name.clone(),
+                    path: Self::trace(&previous, node_id, n.id)?,
+                })
+            })
+            .collect();
+        outputs.sort_by_key(|o| o.node);
+        outputs
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.ports.clear();
+        self.links.clear();
+        self.node_ports.clear();
+        self.port_links.clear();
+        self.node_links.clear();
+        self.link_groups.clear();
+        self.invalidate();
+    }
+
+    /// Adds or replaces a node. Ports that arrived before their node are
+    /// already indexed and show up under it immediately.
+    pub fn add_node(&mut self, mut node: Node) {
+        for port in std::mem::take(&mut node.ports) {
+            self.add_port(port);
+        }
+        self.ungroup(node.id);
+        if let Some(group) = node.properties.get(LINK_GROUP_KEY) {
+            self.link_groups.entry(group.clone()).or_default().insert(node.id);
+        }
+        self.nodes.insert(node.id, node);
+        self.invalidate();
     }
 
     pub fn remove_node(&mut self, id: u32) {
-        self.graph.nodes.retain(|n| n.id != id);
-        self.graph
-            .links
-            .retain(|l| l.output_node != id && l.input_node != id);
+        self.ungroup(id);
+        self.nodes.remove(&id);
+        for port_id in self.node_ports.remove(&id).unwrap_or_default() {
+            self.ports.remove(&port_id);
+            self.port_links.remove(&port_id);
+        }
+        for link_id in self.node_links.remove(&id).unwrap_or_default() {
+            self.remove_link(link_id);
+        }
+        self.invalidate();
+    }
+
+    fn ungroup(&mut self, id: u32) {
+        let Some(group) = self.nodes.get(&id).and_then(|n| n.properties.get(LINK_GROUP_KEY)) else {
+            return;
+        };
+        if let Some(ids) = self.link_groups.get_mut(group) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                self.link_groups.remove(group);
+            }
+        }
     }
 
     pub fn add_port(&mut self, port: Port) {
-        if let Some(node) = self.graph.nodes.iter_mut().find(|n| n.id == port.node_id) {
-            // Remove existing if update
-            node.ports.retain(|p| p.id != port.id);
-            node.ports.push(port);
-        } else {
-            // Store orphan
-            self.orphan_ports
-                .entry(port.node_id)
-                .or_default()
-                .push(port);
+        match self.ports.insert(port.id, port.clone()) {
+            Some(old) if old.node_id != port.node_id => {
+                detach(&mut self.node_ports, old.node_id, port.id);
+                self.node_ports.entry(port.node_id).or_default().push(port.id);
+            }
+            Some(_) => {}
+            None => self.node_ports.entry(port.node_id).or_default().push(port.id),
         }
+        self.invalidate();
     }
 
     pub fn remove_port(&mut self, id: u32) {
-        for node in &mut self.graph.nodes {
-            node.ports.retain(|p| p.id != id);
+        if let Some(port) = self.ports.remove(&id) {
+            detach(&mut self.node_ports, port.node_id, id);
         }
-        // Also check orphans
-        for ports in self.orphan_ports.values_mut() {
-            ports.retain(|p| p.id != id);
+        for link_id in self.port_links.remove(&id).unwrap_or_default() {
+            self.remove_link(link_id);
         }
-
-        self.graph
-            .links
-            .retain(|l| l.output_port != id && l.input_port != id);
+        self.invalidate();
     }
 
     pub fn add_link(&mut self, link: Link) {
-        self.graph.links.retain(|l| l.id != link.id);
-        self.graph.links.push(link);
+        self.remove_link(link.id);
+        for port in [link.output_port, link.input_port] {
+            self.port_links.entry(port).or_default().insert(link.id);
+        }
+        for node in [link.output_node, link.input_node] {
+            self.node_links.entry(node).or_default().insert(link.id);
+        }
+        self.links.insert(link.id, link);
+        self.invalidate();
     }
 
     pub fn remove_link(&mut self, id: u32) {
-        self.graph.links.retain(|l| l.id != id);
+        let Some(link) = self.links.remove(&id) else {
+            return;
+        };
+        for port in [link.output_port, link.input_port] {
+            unindex(&mut self.port_links, port, id);
+        }
+        for node in [link.output_node, link.input_node] {
+            unindex(&mut self.node_links, node, id);
+        }
+        self.invalidate();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Side {
+    /// Takes audio in: a sink or a capture stream.
+    Receiving,
+    /// Passes audio on: a source or a playback stream.
+    Sending,
+}
+
+fn side(node: &Node) -> Option<Side> {
+    let class = node.properties.get("media.class")?;
+    if class.contains("Sink") || class.starts_with("Stream/Input") {
+        Some(Side::Receiving)
+    } else if class.contains("Source") || class.starts_with("Stream/Output") {
+        Some(Side::Sending)
+    } else {
+        None
+    }
+}
+
+/// A sink backed by a device, as opposed to a virtual or loopback sink.
+fn is_hardware_sink(node: &Node) -> bool {
+    node.properties.get("media.class").is_some_and(|c| c == "Audio/Sink")
+        && node.properties.contains_key("device.id")
+}
+
+fn detach(index: &mut HashMap<u32, Vec<u32>>, key: u32, id: u32) {
+    if let Some(ids) = index.get_mut(&key) {
+        ids.retain(|&p| p != id);
+        if ids.is_empty() {
+            index.remove(&key);
+        }
+    }
+}
+
+fn unindex(index: &mut HashMap<u32, HashSet<u32>>, key: u32, id: u32) {
+    if let Some(ids) = index.get_mut(&key) {
+        ids.remove(&id);
+        if ids.is_empty() {
+            index.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::graph::{NodeGroups, NodeType};
+    use std::collections::BTreeMap;
+    use std::time::{Duration, Instant};
+
+    fn node(id: u32) -> Node {
+        Node {
+            id,
+            name: format!("node{}", id),
+            node_type: NodeType::Device,
+            ports: Vec::new(),
+            properties: BTreeMap::new(),
+            groups: NodeGroups::default(),
+        }
+    }
+
+    fn classed(id: u32, class: &str, props: &[(&str, &str)]) -> Node {
+        let mut node = node(id);
+        node.properties.insert("media.class".to_string(), class.to_string());
+        for (k, v) in props {
+            node.properties.insert(k.to_string(), v.to_string());
+        }
+        node
+    }
+
+    fn port(id: u32, node_id: u32, direction: PortDirection) -> Port {
+        Port {
+            id,
+            node_id,
+            name: format!("port{}", id),
+            direction,
+            channel: None,
+            properties: BTreeMap::new(),
+        }
+    }
+
+    fn link(id: u32, output_node: u32, output_port: u32, input_node: u32, input_port: u32) -> Link {
+        Link {
+            id,
+            output_node,
+            output_port,
+            input_node,
+            input_port,
+        }
+    }
+
+    fn port_ids(graph: &GraphManager, node_id: u32) -> Vec<u32> {
+        graph.ports_of(node_id).map(|p| p.id).collect()
+    }
+
+    fn link_ids<'a>(links: impl Iterator<Item = &'a Link>) -> Vec<u32> {
+        let mut ids: Vec<u32> = links.map(|l| l.id).collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Two nodes with one output and one input port each, linked 1 -> 2.
+    fn pair() -> GraphManager {
+        let mut graph = GraphManager::new();
+        graph.add_node(node(1));
+        graph.add_node(node(2));
+        graph.add_port(port(10, 1, PortDirection::Output));
+        graph.add_port(port(20, 2, PortDirection::Input));
+        graph.add_link(link(100, 1, 10, 2, 20));
+        graph
+    }
+
+    #[test]
+    fn indexes_links_by_port_and_node() {
+        let graph = pair();
+        assert_eq!(link_ids(graph.links_of_port(10)), [100]);
+        assert_eq!(link_ids(graph.links_of_port(20)), [100]);
+        assert_eq!(link_ids(graph.links_of_node(1)), [100]);
+        assert_eq!(link_ids(graph.links_of_node(2)), [100]);
+    }
+
+    #[test]
+    fn remove_link_clears_indexes() {
+        let mut graph = pair();
+        graph.remove_link(100);
+        assert!(graph.get_link(100).is_none());
+        assert!(graph.port_links.is_empty());
+        assert!(graph.node_links.is_empty());
+        assert!(graph.get_graph().links.is_empty());
+    }
+
+    #[test]
+    fn remove_port_drops_its_links() {
+        let mut graph = pair();
+        graph.remove_port(10);
+        assert!(graph.get_port(10).is_none());
+        assert!(port_ids(&graph, 1).is_empty());
+        assert!(graph.get_link(100).is_none());
+        assert!(graph.links_of_node(2).next().is_none());
+        assert!(!graph.port_links.contains_key(&20));
+    }
+
+    #[test]
+    fn remove_node_drops_its_ports_and_links() {
+        let mut graph = pair();
+        graph.remove_node(1);
+        assert!(graph.get_node(1).is_none());
+        assert!(graph.get_port(10).is_none());
+        assert!(graph.get_link(100).is_none());
+        assert!(!graph.node_ports.contains_key(&1));
+        assert!(graph.node_links.is_empty());
+        assert!(graph.port_links.is_empty());
+        assert_eq!(port_ids(&graph, 2), [20]);
+    }
+
+    #[test]
+    fn moving_a_port_reindexes_it() {
+        let mut graph = pair();
+        graph.add_port(port(10, 2, PortDirection::Output));
+        assert!(port_ids(&graph, 1).is_empty());
+        assert_eq!(port_ids(&graph, 2), [20, 10]);
+    }
+
+    #[test]
+    fn ports_before_their_node() {
+        let mut graph = GraphManager::new();
+        graph.add_port(port(11, 1, PortDirection::Output));
+        graph.add_port(port(10, 1, PortDirection::Output));
+        assert!(graph.get_graph().nodes.is_empty());
+
+        graph.add_node(node(1));
+        let snapshot = graph.get_graph();
+        let ports: Vec<u32> = snapshot.nodes[0].ports.iter().map(|p| p.id).collect();
+        assert_eq!(ports, [11, 10]);
+    }
+
+    #[test]
+    fn get_graph_orders_by_id_and_refreshes() {
+        let mut graph = GraphManager::new();
+        for id in [5, 3, 9, 1] {
+            graph.add_node(node(id));
+        }
+        graph.add_port(port(30, 3, PortDirection::Output));
+        graph.add_port(port(50, 5, PortDirection::Input));
+        graph.add_link(link(200, 3, 30, 5, 50));
+        graph.add_link(link(150, 3, 30, 5, 50));
+
+        let first = graph.get_graph();
+        let nodes: Vec<u32> = first.nodes.iter().map(|n| n.id).collect();
+        let links: Vec<u32> = first.links.iter().map(|l| l.id).collect();
+        assert_eq!(nodes, [1, 3, 5, 9]);
+        assert_eq!(links, [150, 200]);
+        assert!(Arc::ptr_eq(&first, &graph.get_graph()));
+
+        graph.remove_node(9);
+        let second = graph.get_graph();
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert_eq!(second.nodes.len(), 3);
+    }
+
+    #[test]
+    fn large_interfaces() {
+        // Two 64-channel interfaces, each output fanned out to 64 inputs
+        const CHANNELS: u32 = 64;
+        let mut graph = GraphManager::new();
+        graph.add_node(node(1));
+        graph.add_node(node(2));
+        for c in 0..CHANNELS {
+            graph.add_port(port(1000 + c, 1, PortDirection::Output));
+            graph.add_port(port(2000 + c, 2, PortDirection::Input));
+        }
+        let mut next = 10_000;
+        for o in 0..CHANNELS {
+            for i in 0..CHANNELS {
+                graph.add_link(link(next, 1, 1000 + o, 2, 2000 + i));
+                next += 1;
+            }
+        }
+
+        let snapshot = graph.get_graph();
+        assert_eq!(snapshot.nodes[0].ports.len(), CHANNELS as usize);
+        assert_eq!(snapshot.links.len(), (CHANNELS * CHANNELS) as usize);
+        assert_eq!(graph.links_of_port(1000).count(), CHANNELS as usize);
+        assert_eq!(graph.links_of_node(2).count(), (CHANNELS * CHANNELS) as usize);
+
+        for c in 0..CHANNELS / 2 {
+            graph.remove_port(1000 + c);
+        }
+        assert_eq!(graph.get_graph().links.len(), (CHANNELS * CHANNELS / 2) as usize);
+        assert_eq!(graph.links_of_port(2000).count(), (CHANNELS / 2) as usize);
+
+        graph.remove_node(2);
+        assert!(graph.get_graph().links.is_empty());
+        assert!(graph.port_links.is_empty());
+        assert!(graph.node_links.is_empty());
+        assert_eq!(port_ids(&graph, 1).len(), (CHANNELS / 2) as usize);
+    }
+
+    /// Builds `count` 64-channel interfaces, each output wired to the same
+    /// channel of the next interface, then removes them port by port. Returns
+    /// the time per port spent adding, snapshotting and removing.
+    fn churn(count: u32) -> Duration {
+        const CHANNELS: u32 = 64;
+        let port_id = |n: u32, c: u32, d: u32| n * 1000 + d * 100 + c;
+        let start = Instant::now();
+        let mut graph = GraphManager::new();
+        for n in 0..count {
+            graph.add_node(node(n));
+            for c in 0..CHANNELS {
+                graph.add_port(port(port_id(n, c, 0), n, PortDirection::Output));
+                graph.add_port(port(port_id(n, c, 1), n, PortDirection::Input));
+            }
+        }
+        for n in 0..count {
+            let next = (n + 1) % count;
+            for c in 0..CHANNELS {
+                graph.add_link(link(port_id(n, c, 0), n, port_id(n, c, 0), next, port_id(next, c, 1)));
+            }
+        }
+        let snapshot = graph.get_graph();
+        assert_eq!(snapshot.nodes.len(), count as usize);
+        assert_eq!(snapshot.links.len(), (count * CHANNELS) as usize);
+        assert!((0..count).all(|n| graph.links_of_node(n).count() == 2 * CHANNELS as usize));
+        for n in 0..count {
+            for c in 0..CHANNELS {
+                graph.remove_port(port_id(n, c, 0));
+                graph.remove_port(port_id(n, c, 1));
+            }
+            graph.remove_node(n);
+        }
+        assert!(graph.get_graph().nodes.is_empty());
+        assert!(graph.port_links.is_empty() && graph.node_links.is_empty());
+        start.elapsed() / (count * CHANNELS * 2)
+    }
+
+    #[test]
+    fn thousands_of_ports_scale_linearly() {
+        // 1024 against 8192 ports: with a scan per operation the larger
+        // graph would cost about eight times as much per port
+        let small = (0..3).map(|_| churn(8)).min().unwrap();
+        let large = (0..3).map(|_| churn(64)).min().unwrap();
+        assert!(
+            large < small * 4,
+            "{:?} per port at 8192 ports against {:?} at 1024",
+            large,
+            small
+        );
+    }
+
+    /// A player routed into a loopback whose playback half feeds a sound
+    /// card. The loopback's halves only share `node.link-group`.
+    fn loopback() -> GraphManager {
+        let mut graph = GraphManager::new();
+        graph.add_node(classed(1, "Stream/Output/Audio", &[]));
+        graph.add_node(classed(2, "Audio/Sink", &[(LINK_GROUP_KEY, "loopback-1")]));
+        graph.add_node(classed(3, "Stream/Output/Audio", &[(LINK_GROUP_KEY, "loopback-1")]));
+        graph.add_node(classed(4, "Audio/Sink", &[("device.id", "40")]));
+        graph.add_port(port(10, 1, PortDirection::Output));
+        graph.add_port(port(20, 2, PortDirection::Input));
+        graph.add_port(port(30, 3, PortDirection::Output));
+        graph.add_port(port(40, 4, PortDirection::Input));
+        graph.add_link(link(100, 1, 10, 2, 20));
+        graph.add_link(link(101, 3, 30, 4, 40));
+        graph
+    }
+
+    #[test]
+    fn routes_bridge_link_groups() {
+        let graph = loopback();
+        let outputs = graph.hardware_outputs(1);
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].node, 4);
+        assert_eq!(outputs[0].path, [1, 2, 3, 4]);
+
+        assert_eq!(graph.routes(4, Flow::Upstream).paths, [vec![4, 3, 2, 1]]);
+        // The bridge only runs from the capture half to the playback half
+        assert_eq!(graph.neighbours(3, Flow::Downstream), BTreeSet::from([4]));
+        assert_eq!(graph.neighbours(2, Flow::Upstream), BTreeSet::from([1]));
+    }
+
+    #[test]
+    fn link_groups_follow_node_removal() {
+        let mut graph = loopback();
+        graph.remove_node(3);
+        assert!(graph.hardware_outputs(1).is_empty());
+        assert!(graph.link_groups.get("loopback-1").is_some_and(|ids| ids.len() == 1));
+        graph.remove_node(2);
+        assert!(graph.link_groups.is_empty());
     }
 }
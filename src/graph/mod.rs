@@ -1 +1,9 @@
+pub mod channels;
+pub mod export;
+pub mod groups;
+pub mod layout;
 pub mod manager;
+pub mod presets;
+pub mod qpwgraph;
+pub mod rules;
+pub mod validate;
@@ -0,0 +1,400 @@
+use crate::audio::pipewire::{PipeWireHandler, NODE_NAME_PREFIX};
+use crate::graph::manager::GraphManager;
+use crate::models::graph::{Link, PortDirection};
+use crate::models::preset::{Preset, PresetApply, PresetLink, RemovedLink};
+use crate::utils::broadcast::{EventBroadcaster, ServerEvent};
+use crate::utils::files;
+use anyhow::{bail, Result};
+use parking_lot::{Mutex, RwLock};
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use tracing::info;
+
+// How long the enforcer lets a burst of graph changes settle before acting
+const ENFORCE_SETTLE: Duration = Duration::from_millis(250);
+
+/// Named link presets, kept in a single JSON file that is rewritten on
+/// every change.
+pub struct PresetStore {
+    path: PathBuf,
+    presets: Mutex<BTreeMap<String, Preset>>,
+}
+
+impl PresetStore {
+    /// Loads presets from `path`; a missing file is an empty store.
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let presets = files::read_json::<Vec<Preset>>(&path)?
+            .into_iter()
+            .map(|p| (p.name.clone(), p))
+            .collect();
+        Ok(Self {
+            path,
+            presets: Mutex::new(presets),
+        })
+    }
+
+    /// All presets, ordered by name.
+    pub fn list(&self) -> Vec<Preset> {
+        self.presets.lock().values().cloned().collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Preset> {
+        self.presets.lock().get(name).cloned()
+    }
+
+    /// The preset currently enforced, if any.
+    pub fn enforced(&self) -> Option<Preset> {
+        self.presets.lock().values().find(|p| p.enforced).cloned()
+    }
+
+    /// Stores the preset, replacing any with the same name. Replacing the
+    /// enforced preset keeps it enforced.
+    pub fn save(&self, mut preset: Preset) -> Result<()> {
+        let mut presets = self.presets.lock();
+        let name = preset.name.clone();
+        preset.enforced = presets.get(&name).is_some_and(|p| p.enforced);
+        let old = presets.insert(name.clone(), preset);
+        if let Err(e) = self.persist(&presets) {
+            // Keep memory and disk in agreement
+            match old {
+                Some(old) => presets.insert(name, old),
+                None => presets.remove(&name),
+            };
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Renames a preset. Returns false if there is none named `from`; fails
+    /// if `to` is taken.
+    pub fn rename(&self, from: &str, to: &str) -> Result<bool> {
+        let mut presets = self.presets.lock();
+        if from == to {
+            return Ok(presets.contains_key(from));
+        }
+        if presets.contains_key(to) {
+            bail!("Preset {} already exists", to);
+        }
+        let Some(mut preset) = presets.remove(from) else {
+            return Ok(false);
+        };
+        preset.name = to.to_string();
+        presets.insert(to.to_string(), preset);
+        if let Err(e) = self.persist(&presets) {
+            if let Some(mut preset) = presets.remove(to) {
+                preset.name = from.to_string();
+                presets.insert(from.to_string(), preset);
+            }
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    /// Makes `name` the enforced preset, or stops enforcing with `None`.
+    /// Returns false if there is no such preset.
+    pub fn set_enforced(&self, name: Option<&str>) -> Result<bool> {
+        let mut presets = self.presets.lock();
+        if name.is_some_and(|n| !presets.contains_key(n)) {
+            return Ok(false);
+        }
+        let previous = presets.values().find(|p| p.enforced).map(|p| p.name.clone());
+        for preset in presets.values_mut() {
+            preset.enforced = Some(preset.name.as_str()) == name;
+        }
+        if let Err(e) = self.persist(&presets) {
+            for preset in presets.values_mut() {
+                preset.enforced = Some(&preset.name) == previous.as_ref();
+            }
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<bool> {
+        let mut presets = self.presets.lock();
+        let Some(old) = presets.remove(name) else {
+            return Ok(false);
+        };
+        if let Err(e) = self.persist(&presets) {
+            presets.insert(old.name.clone(), old);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    fn persist(&self, presets: &BTreeMap<String, Preset>) -> Result<()> {
+        files::write_json(&self.path, &presets.values().collect::<Vec<_>>())
+    }
+}
+
+/// The graph's current links as a preset. Links whose endpoints can't be
+/// named and links to the server's own streams are left out.
+pub fn capture(name: &str, graph: &GraphManager) -> Preset {
+    let mut links: Vec<PresetLink> = graph
+        .links()
+        .filter(|link| !is_own_link(graph, link))
+        .filter_map(|link| name_link(graph, link))
+        .collect();
+    links.sort_by(|a, b| {
+        (&a.output_node, &a.output_port, &a.input_node, &a.input_port)
+            .cmp(&(&b.output_node, &b.output_port, &b.input_node, &b.input_port))
+    });
+    links.dedup();
+    Preset {
+        name: name.to_string(),
+        links,
+        saved: files::unix_now(),
+        enforced: false,
+    }
+}
+
+fn name_link(graph: &GraphManager, link: &Link) -> Option<PresetLink> {
+    Some(PresetLink {
+        output_node: graph.get_node(link.output_node)?.name.clone(),
+        output_port: graph.get_port(link.output_port)?.name.clone(),
+        input_node: graph.get_node(link.input_node)?.name.clone(),
+        input_port: graph.get_port(link.input_port)?.name.clone(),
+    })
+}
+
+/// Links to the server's own capture and playback streams belong to
+/// recordings, listeners and meters, not to the routing a preset describes.
+fn is_own_link(graph: &GraphManager, link: &Link) -> bool {
+    [link.output_node, link.input_node].iter().any(|id| {
+        graph
+            .get_node(*id)
+            .is_some_and(|n| n.name.starts_with(NODE_NAME_PREFIX))
+    })
+}
+
+/// A preset matched against the current graph.
+pub struct Resolved {
+    pub apply: PresetApply,
+    /// Ids to pass to `create_link` for each entry of `apply.created`.
+    pub create: Vec<(u32, u32, u32, u32)>,
+}
+
+impl Resolved {
+    pub fn is_noop(&self) -> bool {
+        self.apply.created.is_empty() && self.apply.removed.is_empty()
+    }
+
+    pub fn execute(&self, pw_handler: &PipeWireHandler) {
+        for removed in &self.apply.removed {
+            pw_handler.delete_link(removed.id);
+        }
+        for &(output_node, output_port, input_node, input_port) in &self.create {
+            pw_handler.create_link(output_node, output_port, input_node, input_port);
+        }
+    }
+}
+
+/// Works out which of the preset's links to create and, if `exclusive`,
+/// which existing links to delete because the preset doesn't describe them.
+/// Only links between nodes the graph tracks are candidates; MIDI, video
+/// and other untracked nodes' routing is left alone.
+pub fn resolve(graph: &GraphManager, preset: &Preset, exclusive: bool) -> Resolved {
+    let mut resolved = Resolved {
+        apply: PresetApply::default(),
+        create: Vec::new(),
+    };
+    if exclusive {
+        // Matched by name, so links from every node sharing a name are kept
+        let described: HashSet<&PresetLink> = preset.links.iter().collect();
+        let mut links: Vec<&Link> = graph.links().collect();
+        links.sort_by_key(|l| l.id);
+        for link in links {
+            if is_own_link(graph, link) {
+                continue;
+            }
+            let Some(named) = name_link(graph, link) else {
+                continue;
+            };
+            if !described.contains(&named) {
+                resolved.apply.removed.push(RemovedLink { id: link.id, link: named });
+            }
+        }
+    }
+    for link in &preset.links {
+        let output = graph.find_port(&link.output_node, &link.output_port, PortDirection::Output);
+        let input = graph.find_port(&link.input_node, &link.input_port, PortDirection::Input);
+        let (Some(output), Some(input)) = (output, input) else {
+            resolved.apply.missing.push(link.clone());
+            continue;
+        };
+        if graph.links_of_port(output.id).any(|l| l.input_port == input.id) {
+            resolved.apply.existing.push(link.clone());
+        } else {
+            resolved.apply.created.push(link.clone());
+            resolved.create.push((output.node_id, output.id, input.node_id, input.id));
+        }
+    }
+    resolved
+}
+
+/// Keeps the graph matching the enforced preset: whenever nodes, ports or
+/// links appear, undescribed links are deleted and missing ones created.
+pub async fn enforce(
+    presets: Arc<PresetStore>,
+    graph: Arc<RwLock<GraphManager>>,
+    pw_handler: Arc<PipeWireHandler>,
+    broadcaster: Arc<EventBroadcaster>,
+) {
+    let (mut rx, _) = broadcaster.subscribe_at();
+    loop {
+        match rx.recv().await {
+            Ok(stamped) if !matches!(
+                stamped.event,
+                ServerEvent::DeviceAdded(_) | ServerEvent::PortAdded(_) | ServerEvent::LinkAdded(_)
+            ) => continue,
+            Ok(_) | Err(RecvError::Lagged(_)) => {}
+            Err(RecvError::Closed) => break,
+        }
+        if presets.enforced().is_none() {
+            continue;
+        }
+
+        tokio::time::sleep(ENFORCE_SETTLE).await;
+        while let Ok(_) | Err(TryRecvError::Lagged(_)) = rx.try_recv() {}
+
+        let Some(preset) = presets.enforced() else {
+            continue;
+        };
+        let resolved = resolve(&graph.read(), &preset, true);
+        if resolved.is_noop() {
+            continue;
+        }
+        info!("Enforcing preset {}: creating {} links, removing {}",
+            preset.name, resolved.apply.created.len(), resolved.apply.removed.len());
+        resolved.execute(&pw_handler);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::graph::{Node, NodeGroups, NodeType, Port};
+
+    fn add_node(graph: &mut GraphManager, id: u32, name: &str) {
+        graph.add_node(Node {
+            id,
+            name: name.to_string(),
+            node_type: NodeType::Device,
+            ports: Vec::new(),
+            properties: BTreeMap::new(),
+            groups: NodeGroups::default(),
+        });
+    }
+
+    fn add_port(graph: &mut GraphManager, id: u32, node_id: u32, name: &str, direction: PortDirection) {
+        graph.add_port(Port {
+            id,
+            node_id,
+            name: name.to_string(),
+            direction,
+            channel: None,
+            properties: BTreeMap::new(),
+        });
+    }
+
+    fn add_link(graph: &mut GraphManager, id: u32, output: (u32, u32), input: (u32, u32)) {
+        graph.add_link(Link {
+            id,
+            output_node: output.0,
+            output_port: output.1,
+            input_node: input.0,
+            input_port: input.1,
+        });
+    }
+
+    fn preset(links: Vec<PresetLink>) -> Preset {
+        Preset {
+            name: "test".to_string(),
+            links,
+            saved: 0,
+            enforced: false,
+        }
+    }
+
+    fn named(output_node: &str, output_port: &str, input_node: &str, input_port: &str) -> PresetLink {
+        PresetLink {
+            output_node: output_node.to_string(),
+            output_port: output_port.to_string(),
+            input_node: input_node.to_string(),
+            input_port: input_port.to_string(),
+        }
+    }
+
+    /// A player linked to two sinks, plus a MIDI link between nodes 50 and
+    /// 60 that the graph doesn't track.
+    fn graph() -> GraphManager {
+        let mut graph = GraphManager::new();
+        add_node(&mut graph, 1, "player");
+        add_node(&mut graph, 2, "speakers");
+        add_node(&mut graph, 3, "headphones");
+        add_port(&mut graph, 10, 1, "output_FL", PortDirection::Output);
+        add_port(&mut graph, 20, 2, "playback_FL", PortDirection::Input);
+        add_port(&mut graph, 30, 3, "playback_FL", PortDirection::Input);
+        add_link(&mut graph, 100, (1, 10), (2, 20));
+        add_link(&mut graph, 101, (1, 10), (3, 30));
+        add_link(&mut graph, 200, (50, 51), (60, 61));
+        graph
+    }
+
+    #[test]
+    fn exclusive_removes_only_undescribed_audio_links() {
+        let graph = graph();
+        let preset = preset(vec![named("player", "output_FL", "speakers", "playback_FL")]);
+        let resolved = resolve(&graph, &preset, true);
+        let removed: Vec<u32> = resolved.apply.removed.iter().map(|r| r.id).collect();
+        assert_eq!(removed, [101]);
+        assert_eq!(resolved.apply.existing.len(), 1);
+        assert!(resolved.create.is_empty());
+    }
+
+    #[test]
+    fn exclusive_leaves_untracked_links_alone() {
+        let graph = graph();
+        let resolved = resolve(&graph, &preset(Vec::new()), true);
+        let removed: Vec<u32> = resolved.apply.removed.iter().map(|r| r.id).collect();
+        assert_eq!(removed, [100, 101]);
+    }
+
+    #[test]
+    fn capture_leaves_out_own_streams() {
+        let mut graph = graph();
+        add_node(&mut graph, 4, &format!("{}capture.7", NODE_NAME_PREFIX));
+        add_port(&mut graph, 40, 4, "input_FL", PortDirection::Input);
+        add_link(&mut graph, 102, (1, 10), (4, 40));
+
+        let preset = capture("now", &graph);
+        assert_eq!(
+            preset.links,
+            [
+                named("player", "output_FL", "headphones", "playback_FL"),
+                named("player", "output_FL", "speakers", "playback_FL"),
+            ]
+        );
+        // Restoring it later is a no-op, not a missing link
+        let resolved = resolve(&graph, &preset, true);
+        assert!(resolved.is_noop());
+        assert!(resolved.apply.missing.is_empty());
+    }
+
+    #[test]
+    fn creates_missing_links() {
+        let mut graph = graph();
+        graph.remove_link(100);
+        let preset = preset(vec![
+            named("player", "output_FL", "speakers", "playback_FL"),
+            named("player", "output_FL", "tv", "playback_FL"),
+        ]);
+        let resolved = resolve(&graph, &preset, false);
+        assert_eq!(resolved.create, [(1, 10, 2, 20)]);
+        assert_eq!(resolved.apply.missing.len(), 1);
+        assert!(resolved.apply.removed.is_empty());
+    }
+}
@@ -0,0 +1,324 @@
+use crate::models::preset::{Preset, PresetLink};
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+const VERSION: &str = "0.4.2";
+const AUDIO_PORT_TYPE: &str = "pipewire-audio";
+
+pub struct Patchbay {
+    pub name: Option<String>,
+    pub links: Vec<PresetLink>,
+    /// Items for other port types (MIDI, video, ALSA), which presets don't cover.
+    pub skipped: usize,
+}
+
+#[derive(Default)]
+struct Item {
+    port_type: Option<String>,
+    output: Option<(String, String)>, // node, port
+    input: Option<(String, String)>,
+}
+
+enum Tag {
+    Open { name: String, attrs: HashMap<String, String>, closed: bool },
+    Close(String),
+}
+
+/// Reads a qpwgraph `.qpwgraph` patchbay:
+///
+/// ```xml
+/// <patchbay version="0.4.2" name="studio">
+///  <items>
+///   <item node-type="pipewire" port-type="pipewire-audio">
+///    <output node="Firefox" port="output_FL"/>
+///    <input node="alsa_output.pci-0000_00_1f.3.analog-stereo" port="playback_FL"/>
+///   </item>
+///  </items>
+/// </patchbay>
+/// ```
+///
+/// The format is small and flat enough that a tag scanner covers it.
+pub fn parse(xml: &str) -> Result<Patchbay> {
+    let mut patchbay = Patchbay {
+        name: None,
+        links: Vec::new(),
+        skipped: 0,
+    };
+    let mut seen_root = false;
+    let mut item: Option<Item> = None;
+
+    for tag in scan(xml)? {
+        match tag {
+            Tag::Open { name, attrs, closed } => match name.as_str() {
+                "patchbay" => {
+                    seen_root = true;
+                    patchbay.name = attrs.get("name").filter(|n| !n.is_empty()).cloned();
+                }
+                "item" if !closed => {
+                    item = Some(Item {
+                        port_type: attrs.get("port-type").cloned(),
+                        ..Default::default()
+                    })
+                }
+                "output" | "input" => {
+                    let Some(item) = item.as_mut() else {
+                        bail!("<{}> outside of <item>", name);
+                    };
+                    let endpoint = (
+                        attrs.get("node").cloned().context("Endpoint without node")?,
+                        attrs.get("port").cloned().context("Endpoint without port")?,
+                    );
+                    if name == "output" {
+                        item.output = Some(endpoint);
+                    } else {
+                        item.input = Some(endpoint);
+                    }
+                }
+                _ => {}
+            },
+            Tag::Close(name) if name == "item" => {
+                let Some(item) = item.take() else {
+                    bail!("Unbalanced </item>");
+                };
+                if item.port_type.as_deref().is_some_and(|t| t != AUDIO_PORT_TYPE) {
+                    patchbay.skipped += 1;
+                    continue;
+                }
+                let (Some((output_node, output_port)), Some((input_node, input_port))) = (item.output, item.input) else {
+                    bail!("<item> without both <output> and <input>");
+                };
+                let link = PresetLink {
+                    output_node,
+                    output_port,
+                    input_node,
+                    input_port,
+                };
+                if !patchbay.links.contains(&link) {
+                    patchbay.links.push(link);
+                }
+            }
+            Tag::Close(_) => {}
+        }
+    }
+    if !seen_root {
+        bail!("Not a qpwgraph patchbay: no <patchbay> element");
+    }
+    Ok(patchbay)
+}
+
+/// Writes a preset as a qpwgraph patchbay of audio links.
+pub fn write(preset: &Preset) -> String {
+    let mut xml = String::from("<!DOCTYPE patchbay>\n");
+    let _ = writeln!(xml, "<patchbay version=\"{}\" name=\"{}\">", VERSION, escape(&preset.name));
+    xml.push_str(" <items>\n");
+    for link in &preset.links {
+        let _ = writeln!(xml, "  <item node-type=\"pipewire\" port-type=\"{}\">", AUDIO_PORT_TYPE);
+        let _ = writeln!(xml, "   <output node=\"{}\" port=\"{}\"/>", escape(&link.output_node), escape(&link.output_port));
+        let _ = writeln!(xml, "   <input node=\"{}\" port=\"{}\"/>", escape(&link.input_node), escape(&link.input_port));
+        xml.push_str("  </item>\n");
+    }
+    xml.push_str(" </items>\n</patchbay>\n");
+    xml
+}
+
+fn scan(xml: &str) -> Result<Vec<Tag>> {
+    let mut tags = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        // Declarations, comments and the doctype carry nothing we need
+        let skip_to = if rest.starts_with("<!--") {
+            Some("-->")
+        } else if rest.starts_with("<?") {
+            Some("?>")
+        } else if rest.starts_with("<!") {
+            Some(">")
+        } else {
+            None
+        };
+        if let Some(terminator) = skip_to {
+            let end = rest.find(terminator).context("Unterminated markup")?;
+            rest = &rest[end + terminator.len()..];
+            continue;
+        }
+
+        let end = tag_end(rest).context("Unterminated tag")?;
+        let body = &rest[1..end];
+        rest = &rest[end + 1..];
+        if let Some(name) = body.strip_prefix('/') {
+            tags.push(Tag::Close(name.trim().to_string()));
+            continue;
+        }
+        let (body, closed) = match body.strip_suffix('/') {
+            Some(body) => (body, true),
+            None => (body, false),
+        };
+        let name_end = body.find(|c: char| c.is_whitespace()).unwrap_or(body.len());
+        tags.push(Tag::Open {
+            name: body[..name_end].to_string(),
+            attrs: attributes(&body[name_end..])?,
+            closed,
+        });
+    }
+    Ok(tags)
+}
+
+/// Index of the `>` ending the tag at the start of `s`, skipping quoted values.
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn attributes(mut s: &str) -> Result<HashMap<String, String>> {
+    let mut attrs = HashMap::new();
+    loop {
+        s = s.trim_start();
+        if s.is_empty() {
+            return Ok(attrs);
+        }
+        let eq = s.find('=').context("Attribute without value")?;
+        let name = s[..eq].trim().to_string();
+        s = s[eq + 1..].trim_start();
+        let quote = s.chars().next().filter(|c| *c == '"' || *c == '\'').context("Unquoted attribute")?;
+        let close = s[1..].find(quote).context("Unterminated attribute")? + 1;
+        attrs.insert(name, unescape(&s[1..close])?);
+        s = &s[close + 1..];
+    }
+}
+
+/// Escapes text for XML attribute values and content.
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(s: &str) -> Result<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let semi = rest.find(';').context("Unterminated entity")?;
+        let entity = &rest[1..semi];
+        let c = match entity {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#').and_then(|d| d.parse().ok()),
+                };
+                code.and_then(char::from_u32)
+                    .with_context(|| format!("Unknown entity &{};", entity))?
+            }
+        };
+        out.push(c);
+        rest = &rest[semi + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STUDIO: &str = include_str!("../../tests/fixtures/studio.qpwgraph");
+
+    fn link(output_node: &str, output_port: &str, input_node: &str, input_port: &str) -> PresetLink {
+        PresetLink {
+            output_node: output_node.to_string(),
+            output_port: output_port.to_string(),
+            input_node: input_node.to_string(),
+            input_port: input_port.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_sample_file() {
+        let patchbay = parse(STUDIO).unwrap();
+        assert_eq!(patchbay.name.as_deref(), Some("studio"));
+        assert_eq!(patchbay.skipped, 2);
+        let sink = "alsa_output.pci-0000_00_1f.3.analog-stereo";
+        assert_eq!(
+            patchbay.links,
+            [
+                link("Firefox", "output_FL", sink, "playback_FL"),
+                link("Firefox", "output_FR", sink, "playback_FR"),
+                link("Drums & Bass", "capture_MONO", "Mixer \"Main\"", "input_FL"),
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips_awkward_names() {
+        let preset = Preset {
+            name: "Live <A&B> \"set\"".to_string(),
+            links: vec![
+                link("R&D <synth>", "out \"L\"", "a'b", "in&1"),
+                link("plain", "output_FL", "sink", "playback_FL"),
+            ],
+            saved: 0,
+            enforced: false,
+        };
+        let patchbay = parse(&write(&preset)).unwrap();
+        assert_eq!(patchbay.name.as_deref(), Some(preset.name.as_str()));
+        assert_eq!(patchbay.links, preset.links);
+        assert_eq!(patchbay.skipped, 0);
+    }
+
+    #[test]
+    fn sample_survives_write_and_parse() {
+        let first = parse(STUDIO).unwrap();
+        let preset = Preset {
+            name: first.name.clone().unwrap(),
+            links: first.links.clone(),
+            saved: 0,
+            enforced: false,
+        };
+        assert_eq!(parse(&write(&preset)).unwrap().links, first.links);
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        let cases = [
+            // Unbalanced </item>
+            "<patchbay><items></item></items></patchbay>",
+            // Endpoint without port
+            "<patchbay><items><item port-type=\"pipewire-audio\"><output node=\"a\"/><input node=\"b\" port=\"p\"/></item></items></patchbay>",
+            // Item without an input
+            "<patchbay><items><item port-type=\"pipewire-audio\"><output node=\"a\" port=\"p\"/></item></items></patchbay>",
+            // Unterminated tag
+            "<patchbay><items><item",
+            // Unknown entity
+            "<patchbay name=\"&nope;\"></patchbay>",
+            // No root element
+            "<items></items>",
+        ];
+        for xml in cases {
+            assert!(parse(xml).is_err(), "accepted {}", xml);
+        }
+    }
+}
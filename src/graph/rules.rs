@@ -0,0 +1,654 @@
+use crate::audio::controller::{AudioController, UNITY_VOLUME};
+use crate::audio::pipewire::PipeWireHandler;
+use crate::graph::manager::GraphManager;
+use crate::models::graph::{Node, Port, PortDirection};
+use crate::models::rule::{LinkSide, Pattern, PropertyMatch, Rule, RuleAction, RuleFiring, RuleSpec};
+use crate::utils::broadcast::ServerEvent;
+use crate::utils::files;
+use anyhow::{bail, Context, Result};
+use parking_lot::Mutex;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::Hash;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+const LOG_LEN: usize = 256;
+
+/// How long a requested link or unlink holds off repeats of itself. Failed
+/// `pw-link` runs aren't reported back, so after this a rule tries again.
+const PENDING_TTL: Duration = Duration::from_secs(5);
+
+struct Matcher {
+    key: String,
+    regex: Regex,
+}
+
+impl Matcher {
+    fn compile(m: &PropertyMatch) -> Result<Self> {
+        let source = match &m.pattern {
+            Pattern::Glob(glob) => glob_to_regex(glob),
+            Pattern::Regex(regex) => regex.clone(),
+        };
+        let regex = Regex::new(&source).with_context(|| format!("Invalid pattern for {}", m.key))?;
+        Ok(Self {
+            key: m.key.clone(),
+            regex,
+        })
+    }
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+fn compile_all(matches: &[PropertyMatch]) -> Result<Vec<Matcher>> {
+    matches.iter().map(Matcher::compile).collect()
+}
+
+/// True if every matcher's property is present and matches. A missing
+/// property never matches.
+fn matches_all(matchers: &[Matcher], properties: &BTreeMap<String, String>) -> bool {
+    matchers
+        .iter()
+        .all(|m| properties.get(&m.key).is_some_and(|v| m.regex.is_match(v)))
+}
+
+enum Action {
+    Link { target: Vec<Matcher>, side: LinkSide, ports: Vec<Matcher> },
+    Unlink { target: Option<Vec<Matcher>>, side: LinkSide, ports: Vec<Matcher> },
+    Volume(f32),
+    Mute(bool),
+    Move(Vec<Matcher>),
+}
+
+struct Compiled {
+    rule: Rule,
+    node: Vec<Matcher>,
+    actions: Vec<Action>,
+}
+
+impl Compiled {
+    fn new(rule: Rule) -> Result<Self> {
+        let spec = &rule.spec;
+        if spec.name.trim().is_empty() {
+            bail!("Rule name must not be empty");
+        }
+        if spec.node.is_empty() {
+            bail!("Rule must match on at least one node property");
+        }
+        if spec.actions.is_empty() {
+            bail!("Rule must have at least one action");
+        }
+        let mut actions = Vec::new();
+        for action in &spec.actions {
+            actions.push(match action {
+                RuleAction::Link { target, side, ports } => {
+                    if target.is_empty() {
+                        bail!("Link target must match on at least one property");
+                    }
+                    Action::Link { target: compile_all(target)?, side: *side, ports: compile_all(ports)? }
+                }
+                RuleAction::Unlink { target, side, ports } => Action::Unlink {
+                    target: target.as_deref().map(compile_all).transpose()?,
+                    side: *side,
+                    ports: compile_all(ports)?,
+                },
+                RuleAction::Volume { volume } => {
+                    if !volume.is_finite() || *volume < 0.0 {
+                        bail!("Volume must be a non-negative number");
+                    }
+                    Action::Volume(*volume)
+                }
+                RuleAction::Mute { muted } => Action::Mute(*muted),
+                RuleAction::Move { target } => {
+                    if target.is_empty() {
+                        bail!("Move target must match on at least one property");
+                    }
+                    Action::Move(compile_all(target)?)
+                }
+            });
+        }
+        Ok(Self {
+            node: compile_all(&spec.node)?,
+            actions,
+            rule,
+        })
+    }
+}
+
+/// What set of actions an event calls for.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Trigger {
+    /// A node appeared: everything.
+    Node,
+    /// A port appeared: links only.
+    Port,
+    /// A link appeared: unlinks only.
+    Link,
+}
+
+/// Auto-connect rules, evaluated by the event loop as nodes, ports and
+/// links appear. Rules are kept in a JSON file rewritten on every change.
+pub struct RuleEngine {
+    path: PathBuf,
+    rules: Mutex<Vec<Compiled>>,
+    log: Mutex<VecDeque<RuleFiring>>,
+    // (output port, input port) of links requested but not seen yet
+    pending: Mutex<HashMap<(u32, u32), Instant>>,
+    // Links asked to be deleted but not gone yet
+    deleting: Mutex<HashMap<u32, Instant>>,
+    pw_handler: Arc<PipeWireHandler>,
+}
+
+impl RuleEngine {
+    /// Loads rules from `path`; a missing file means no rules.
+    pub fn load(path: PathBuf, pw_handler: Arc<PipeWireHandler>) -> Result<Self> {
+        let rules: Vec<Rule> = files::read_json(&path)?;
+        let rules = rules
+            .into_iter()
+            .map(|rule| {
+                let id = rule.id;
+                Compiled::new(rule).with_context(|| format!("Invalid rule {}", id))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            path,
+            rules: Mutex::new(rules),
+            log: Mutex::new(VecDeque::with_capacity(LOG_LEN)),
+            pending: Mutex::new(HashMap::new()),
+            deleting: Mutex::new(HashMap::new()),
+            pw_handler,
+        })
+    }
+
+    /// Checks that a rule is well-formed and its patterns compile.
+    pub fn check(spec: &RuleSpec) -> Result<()> {
+        Compiled::new(Rule { id: 0, spec: spec.clone() }).map(|_| ())
+    }
+
+    pub fn list(&self) -> Vec<Rule> {
+        self.rules.lock().iter().map(|c| c.rule.clone()).collect()
+    }
+
+    /// Fired rules, newest first.
+    pub fn log(&self) -> Vec<RuleFiring> {
+        self.log.lock().iter().rev().cloned().collect()
+    }
+
+    pub fn create(&self, spec: RuleSpec) -> Result<Rule> {
+        let mut rules = self.rules.lock();
+        let id = rules.iter().map(|c| c.rule.id).max().unwrap_or(0) + 1;
+        let rule = Rule { id, spec };
+        rules.push(Compiled::new(rule.clone())?);
+        if let Err(e) = self.persist(&rules) {
+            rules.pop();
+            return Err(e);
+        }
+        Ok(rule)
+    }
+
+    /// Replaces a rule. Returns `None` if there is no rule with that id.
+    pub fn update(&self, id: u32, spec: RuleSpec) -> Result<Option<Rule>> {
+        let mut rules = self.rules.lock();
+        let Some(index) = rules.iter().position(|c| c.rule.id == id) else {
+            return Ok(None);
+        };
+        let rule = Rule { id, spec };
+        let old = std::mem::replace(&mut rules[index], Compiled::new(rule.clone())?);
+        if let Err(e) = self.persist(&rules) {
+            rules[index] = old;
+            return Err(e);
+        }
+        Ok(Some(rule))
+    }
+
+    pub fn delete(&self, id: u32) -> Result<bool> {
+        let mut rules = self.rules.lock();
+        let Some(index) = rules.iter().position(|c| c.rule.id == id) else {
+            return Ok(false);
+        };
+        let old = rules.remove(index);
+        if let Err(e) = self.persist(&rules) {
+            rules.insert(index, old);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    fn persist(&self, rules: &[Compiled]) -> Result<()> {
+        files::write_json(&self.path, &rules.iter().map(|c| &c.rule).collect::<Vec<_>>())
+    }
+
+    /// Runs the rules an event triggers against the graph and devices it has
+    /// just been applied to.
+    pub fn handle(&self, audio: &AudioController, graph: &GraphManager, event: &ServerEvent) {
+        match event {
+            ServerEvent::DeviceAdded(device) => self.run(audio, graph, device.id, Trigger::Node),
+            ServerEvent::PortAdded(port) => {
+                self.run(audio, graph, port.node_id, Trigger::Port);
+                self.run_towards(audio, graph, port.node_id);
+            }
+            ServerEvent::PortRemoved(id) => {
+                self.pending.lock().retain(|&(out, input), _| out != *id && input != *id);
+            }
+            ServerEvent::LinkAdded(link) => {
+                self.pending.lock().remove(&(link.output_port, link.input_port));
+                self.run(audio, graph, link.output_node, Trigger::Link);
+                self.run(audio, graph, link.input_node, Trigger::Link);
+            }
+            ServerEvent::LinkRemoved(id) => {
+                self.deleting.lock().remove(id);
+            }
+            _ => {}
+        }
+    }
+
+    fn run(&self, audio: &AudioController, graph: &GraphManager, node_id: u32, trigger: Trigger) {
+        let Some(node) = graph.get_node(node_id) else {
+            return;
+        };
+        let rules = self.rules.lock();
+        for compiled in rules.iter() {
+            if !compiled.rule.spec.enabled || !matches_all(&compiled.node, &node.properties) {
+                continue;
+            }
+            let effects: Vec<String> = compiled
+                .actions
+                .iter()
+                .filter(|action| match action {
+                    Action::Link { .. } => trigger != Trigger::Link,
+                    Action::Unlink { .. } => trigger != Trigger::Port,
+                    Action::Volume(_) | Action::Mute(_) | Action::Move(_) => trigger == Trigger::Node,
+                })
+                .flat_map(|action| self.apply(audio, graph, node, action))
+                .collect();
+            self.record(&compiled.rule, node, effects);
+        }
+    }
+
+    /// Link actions whose target is `node_id`, for the nodes they match: the
+    /// target may show up after the node the rule is about.
+    fn run_towards(&self, audio: &AudioController, graph: &GraphManager, node_id: u32) {
+        let Some(target) = graph.get_node(node_id) else {
+            return;
+        };
+        let rules = self.rules.lock();
+        for compiled in rules.iter().filter(|c| c.rule.spec.enabled) {
+            let links: Vec<&Action> = compiled
+                .actions
+                .iter()
+                .filter(|a| matches!(a, Action::Link { target: t, .. } if matches_all(t, &target.properties)))
+                .collect();
+            if links.is_empty() {
+                continue;
+            }
+            let mut nodes: Vec<&Node> = graph
+                .nodes()
+                .filter(|n| n.id != node_id && matches_all(&compiled.node, &n.properties))
+                .collect();
+            nodes.sort_by_key(|n| n.id);
+            for node in nodes {
+                let effects = links.iter().flat_map(|a| self.apply(audio, graph, node, a)).collect();
+                self.record(&compiled.rule, node, effects);
+            }
+        }
+    }
+
+    fn record(&self, rule: &Rule, node: &Node, effects: Vec<String>) {
+        if effects.is_empty() {
+            return;
+        }
+        info!("Rule {} ({}) fired on {}: {}", rule.id, rule.spec.name, node.name, effects.join(", "));
+        let mut log = self.log.lock();
+        if log.len() == LOG_LEN {
+            log.pop_front();
+        }
+        log.push_back(RuleFiring {
+            time: files::unix_now(),
+            rule_id: rule.id,
+            rule_name: rule.spec.name.clone(),
+            node_id: node.id,
+            node_name: node.name.clone(),
+            effects,
+        });
+    }
+
+    /// Carries out one action for `node` and describes what it did.
+    fn apply(&self, audio: &AudioController, graph: &GraphManager, node: &Node, action: &Action) -> Vec<String> {
+        match action {
+            Action::Link { target, side, ports } => {
+                let Some(other) = find_node(graph, target, node.id) else {
+                    return Vec::new();
+                };
+                let (outputs, inputs) = match side {
+                    LinkSide::Output => (
+                        ports_on(graph, node.id, PortDirection::Output, ports),
+                        ports_on(graph, other.id, PortDirection::Input, &[]),
+                    ),
+                    LinkSide::Input => (
+                        ports_on(graph, other.id, PortDirection::Output, &[]),
+                        ports_on(graph, node.id, PortDirection::Input, ports),
+                    ),
+                };
+                let mut pending = self.pending.lock();
+                let now = Instant::now();
+                let mut effects = Vec::new();
+                for (output, input) in pair_ports(&outputs, &inputs) {
+                    let linked = graph.links_of_port(output.id).any(|l| l.input_port == input.id);
+                    if linked || !claim(&mut pending, (output.id, input.id), now) {
+                        continue;
+                    }
+                    self.pw_handler.create_link(output.node_id, output.id, input.node_id, input.id);
+                    effects.push(format!("link {} -> {}", output.id, input.id));
+                }
+                effects
+            }
+            Action::Unlink { target, side, ports } => {
+                let direction = match side {
+                    LinkSide::Output => PortDirection::Output,
+                    LinkSide::Input => PortDirection::Input,
+                };
+                let mut effects = Vec::new();
+                for port in ports_on(graph, node.id, direction, ports) {
+                    let mut links: Vec<_> = graph.links_of_port(port.id).collect();
+                    links.sort_by_key(|l| l.id);
+                    for link in links {
+                        let other = match side {
+                            LinkSide::Output => link.input_node,
+                            LinkSide::Input => link.output_node,
+                        };
+                        let wanted = match target {
+                            None => true,
+                            Some(target) => graph
+                                .get_node(other)
+                                .is_some_and(|n| matches_all(target, &n.properties)),
+                        };
+                        if wanted && claim(&mut self.deleting.lock(), link.id, Instant::now()) {
+                            self.pw_handler.delete_link(link.id);
+                            effects.push(format!("unlink {}", link.id));
+                        }
+                    }
+                }
+                effects
+            }
+            Action::Volume(volume) => {
+                // Rules get no more headroom than the device's boost setting allows
+                let volume = volume.min(audio.max_volume(node.id).unwrap_or(UNITY_VOLUME));
+                self.pw_handler.set_volume(node.id, volume, None);
+                vec![format!("volume {}", volume)]
+            }
+            Action::Mute(muted) => {
+                self.pw_handler.set_mute(node.id, *muted);
+                vec![format!("mute {}", muted)]
+            }
+            Action::Move(target) => {
+                let Some(other) = find_node(graph, target, node.id) else {
+                    return Vec::new();
+                };
+                self.pw_handler.move_stream(node.id, &other.name);
+                vec![format!("move to {}", other.name)]
+            }
+        }
+    }
+}
+
+/// Marks `key` as requested at `now`. False if it already was, less than
+/// `PENDING_TTL` ago.
+fn claim<K: Hash + Eq>(requested: &mut HashMap<K, Instant>, key: K, now: Instant) -> bool {
+    requested.retain(|_, at| now.duration_since(*at) < PENDING_TTL);
+    if requested.contains_key(&key) {
+        return false;
+    }
+    requested.insert(key, now);
+    true
+}
+
+/// The lowest-id node other than `except` matching every matcher.
+fn find_node<'a>(graph: &'a GraphManager, matchers: &[Matcher], except: u32) -> Option<&'a Node> {
+    graph
+        .nodes()
+        .filter(|n| n.id != except && matches_all(matchers, &n.properties))
+        .min_by_key(|n| n.id)
+}
+
+fn ports_on<'a>(graph: &'a GraphManager, node_id: u32, direction: PortDirection, matchers: &[Matcher]) -> Vec<&'a Port> {
+    graph
+        .ports_of(node_id)
+        .filter(|p| p.direction == direction && matches_all(matchers, &p.properties))
+        .collect()
+}
+
+/// Pairs ports in order; a single port on either side fans out to all of
+/// the other side.
+fn pair_ports<'a>(outputs: &[&'a Port], inputs: &[&'a Port]) -> Vec<(&'a Port, &'a Port)> {
+    match (outputs, inputs) {
+        ([output], inputs) => inputs.iter().map(|input| (*output, *input)).collect(),
+        (outputs, [input]) => outputs.iter().map(|output| (*output, *input)).collect(),
+        (outputs, inputs) => outputs.iter().copied().zip(inputs.iter().copied()).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::pipewire::PwCommand;
+    use crate::models::device::{AudioDevice, DeviceState, DeviceType};
+    use crate::models::graph::{Link, NodeGroups, NodeType};
+    use crossbeam_channel::Receiver;
+
+    fn glob(key: &str, pattern: &str) -> PropertyMatch {
+        PropertyMatch { key: key.to_string(), pattern: Pattern::Glob(pattern.to_string()) }
+    }
+
+    fn props(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn node(id: u32, app: &str) -> Node {
+        Node {
+            id,
+            name: app.to_string(),
+            node_type: NodeType::Application,
+            ports: Vec::new(),
+            properties: props(&[("application.name", app)]),
+            groups: NodeGroups::default(),
+        }
+    }
+
+    fn port(id: u32, node_id: u32, direction: PortDirection, channel: &str) -> Port {
+        Port {
+            id,
+            node_id,
+            name: format!("port_{}", channel),
+            direction,
+            channel: Some(channel.to_string()),
+            properties: props(&[("audio.channel", channel)]),
+        }
+    }
+
+    fn device(id: u32, name: &str) -> AudioDevice {
+        AudioDevice {
+            id,
+            name: name.to_string(),
+            description: name.to_string(),
+            device_type: DeviceType::Sink,
+            state: DeviceState::Idle,
+            channels: Vec::new(),
+            muted: false,
+            base_volume: 1.0,
+            boost: false,
+            properties: BTreeMap::new(),
+        }
+    }
+
+    /// A player (1) with outputs FL/FR and a sink (2) with inputs FL/FR.
+    fn graph() -> GraphManager {
+        let mut graph = GraphManager::new();
+        graph.add_node(node(1, "player"));
+        graph.add_node(node(2, "sink"));
+        graph.add_port(port(10, 1, PortDirection::Output, "FL"));
+        graph.add_port(port(11, 1, PortDirection::Output, "FR"));
+        graph.add_port(port(20, 2, PortDirection::Input, "FL"));
+        graph.add_port(port(21, 2, PortDirection::Input, "FR"));
+        graph
+    }
+
+    /// An engine with one rule for the player: link to the sink, set a volume.
+    fn engine(actions: Vec<RuleAction>) -> (RuleEngine, Receiver<PwCommand>) {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let rule = Rule {
+            id: 1,
+            spec: RuleSpec {
+                name: "player".to_string(),
+                enabled: true,
+                node: vec![glob("application.name", "play*")],
+                actions,
+            },
+        };
+        let engine = RuleEngine {
+            path: PathBuf::new(),
+            rules: Mutex::new(vec![Compiled::new(rule).unwrap()]),
+            log: Mutex::new(VecDeque::new()),
+            pending: Mutex::new(HashMap::new()),
+            deleting: Mutex::new(HashMap::new()),
+            pw_handler: Arc::new(PipeWireHandler::with_sender(tx)),
+        };
+        (engine, rx)
+    }
+
+    fn link_to_sink() -> RuleAction {
+        RuleAction::Link { target: vec![glob("application.name", "sink")], side: LinkSide::Output, ports: Vec::new() }
+    }
+
+    fn sent(rx: &Receiver<PwCommand>) -> Vec<String> {
+        rx.try_iter()
+            .map(|command| match command {
+                PwCommand::CreateLink(_, out, _, input) => format!("link {} {}", out, input),
+                PwCommand::DeleteLink(id) => format!("unlink {}", id),
+                PwCommand::SetVolume(id, volume, _) => format!("volume {} {}", id, volume),
+                _ => "other".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn globs_anchor_and_escape() {
+        let matches = |pattern: &str, value: &str| Regex::new(&glob_to_regex(pattern)).unwrap().is_match(value);
+        assert!(matches("OBS*", "OBS Studio"));
+        assert!(!matches("OBS*", "my OBS"));
+        assert!(matches("in_?", "in_1"));
+        assert!(!matches("in_?", "in_12"));
+        assert!(matches("a.b (1)", "a.b (1)"));
+        assert!(!matches("a.b", "axb"));
+    }
+
+    #[test]
+    fn every_property_must_match() {
+        let matchers = compile_all(&[glob("application.name", "Fire*"), glob("media.role", "Music")]).unwrap();
+        assert!(matches_all(&matchers, &props(&[("application.name", "Firefox"), ("media.role", "Music")])));
+        assert!(!matches_all(&matchers, &props(&[("application.name", "Firefox"), ("media.role", "Game")])));
+        // A missing property never matches, not even `*`
+        let any = compile_all(&[glob("media.role", "*")]).unwrap();
+        assert!(!matches_all(&any, &props(&[("application.name", "Firefox")])));
+        assert!(matches_all(&[], &BTreeMap::new()));
+    }
+
+    #[test]
+    fn pairs_in_order_and_fans_out() {
+        let out: Vec<Port> = (0..3).map(|i| port(10 + i, 1, PortDirection::Output, "X")).collect();
+        let inp: Vec<Port> = (0..2).map(|i| port(20 + i, 2, PortDirection::Input, "X")).collect();
+        let ids = |outputs: &[Port], inputs: &[Port]| -> Vec<(u32, u32)> {
+            let outputs: Vec<&Port> = outputs.iter().collect();
+            let inputs: Vec<&Port> = inputs.iter().collect();
+            pair_ports(&outputs, &inputs).iter().map(|(o, i)| (o.id, i.id)).collect()
+        };
+        assert_eq!(ids(&out[..2], &inp), [(10, 20), (11, 21)]);
+        assert_eq!(ids(&out, &inp), [(10, 20), (11, 21)]);
+        assert_eq!(ids(&out[..1], &inp), [(10, 20), (10, 21)]);
+        assert_eq!(ids(&out, &inp[..1]), [(10, 20), (11, 20), (12, 20)]);
+        assert!(ids(&out, &[]).is_empty());
+    }
+
+    #[test]
+    fn actions_follow_their_trigger() {
+        let (engine, rx) = engine(vec![link_to_sink(), RuleAction::Volume { volume: 10.0 }]);
+        let audio = AudioController::new().unwrap();
+        let graph = graph();
+
+        // A port only calls for links
+        engine.handle(&audio, &graph, &ServerEvent::PortAdded(graph.get_port(10).unwrap().clone()));
+        assert_eq!(sent(&rx), ["link 10 20", "link 11 21"]);
+
+        // A new node runs everything; the volume is held to 100% without boost
+        engine.pending.lock().clear();
+        engine.handle(&audio, &graph, &ServerEvent::DeviceAdded(device(1, "player")));
+        assert_eq!(sent(&rx), ["link 10 20", "link 11 21", "volume 1 1"]);
+    }
+
+    #[test]
+    fn unlinks_only_on_new_links() {
+        let unlink = RuleAction::Unlink { target: None, side: LinkSide::Output, ports: Vec::new() };
+        let (engine, rx) = engine(vec![unlink]);
+        let audio = AudioController::new().unwrap();
+        let mut graph = graph();
+        let link = Link { id: 100, output_node: 1, output_port: 10, input_node: 2, input_port: 20 };
+        graph.add_link(link.clone());
+
+        engine.handle(&audio, &graph, &ServerEvent::PortAdded(graph.get_port(11).unwrap().clone()));
+        assert!(sent(&rx).is_empty());
+        engine.handle(&audio, &graph, &ServerEvent::LinkAdded(link.clone()));
+        assert_eq!(sent(&rx), ["unlink 100"]);
+        // Still deleting: the same link showing up again isn't unlinked twice
+        engine.handle(&audio, &graph, &ServerEvent::LinkAdded(link));
+        assert!(sent(&rx).is_empty());
+    }
+
+    #[test]
+    fn pending_links_are_requested_once() {
+        let (engine, rx) = engine(vec![link_to_sink()]);
+        let audio = AudioController::new().unwrap();
+        let mut graph = graph();
+        let event = ServerEvent::PortAdded(graph.get_port(10).unwrap().clone());
+
+        engine.handle(&audio, &graph, &event);
+        engine.handle(&audio, &graph, &event);
+        assert_eq!(sent(&rx), ["link 10 20", "link 11 21"]);
+
+        // Once the link exists it's no longer pending, nor requested again
+        let link = Link { id: 100, output_node: 1, output_port: 10, input_node: 2, input_port: 20 };
+        graph.add_link(link.clone());
+        engine.handle(&audio, &graph, &ServerEvent::LinkAdded(link));
+        assert_eq!(engine.pending.lock().len(), 1);
+        engine.pending.lock().clear();
+        engine.handle(&audio, &graph, &event);
+        assert_eq!(sent(&rx), ["link 11 21"]);
+
+        // A removed port lets its pairs be requested anew
+        engine.handle(&audio, &graph, &ServerEvent::PortRemoved(21));
+        assert!(engine.pending.lock().is_empty());
+    }
+
+    #[test]
+    fn claims_expire() {
+        let mut requested = HashMap::new();
+        let now = Instant::now();
+        assert!(claim(&mut requested, (10, 20), now));
+        assert!(!claim(&mut requested, (10, 20), now + PENDING_TTL / 2));
+        assert!(claim(&mut requested, (11, 21), now));
+        // A request that never produced a link may be retried
+        assert!(claim(&mut requested, (10, 20), now + PENDING_TTL));
+        assert_eq!(requested.len(), 1);
+    }
+}
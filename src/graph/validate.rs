@@ -0,0 +1,201 @@
+use crate::graph::manager::GraphManager;
+use crate::models::graph::PortDirection;
+use serde::Serialize;
+
+/// Port property describing the sample format, e.g. `32 bit float mono audio`
+/// or `8 bit raw midi`.
+pub const FORMAT_KEY: &str = "format.dsp";
+
+/// Why a requested link was refused. Serialized as
+/// `{"error": "wrong_direction", "port": 51, "expected": "Output"}`.
+#[derive(Debug, Clone, Serialize, thiserror::Error)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum LinkError {
+    #[error("node {node} does not exist")]
+    NodeNotFound { node: u32 },
+    #[error("port {port} does not exist")]
+    PortNotFound { port: u32 },
+    #[error("port {port} belongs to node {actual_node}, not {node}")]
+    NodeMismatch { port: u32, node: u32, actual_node: u32 },
+    #[error("port {port} is not an {expected:?} port")]
+    WrongDirection { port: u32, expected: PortDirection },
+    #[error("cannot link {output} to {input}")]
+    MediaMismatch { output: String, input: String },
+    #[error("ports are already linked by link {link}")]
+    LinkExists { link: u32 },
+    /// The new link would close a loop; `path` runs from the output node
+    /// through the existing links back to itself.
+    #[error("link would create a feedback loop through nodes {}", join_path(path))]
+    Cycle { path: Vec<u32> },
+}
+
+impl LinkError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NodeNotFound { .. } => "node_not_found",
+            Self::PortNotFound { .. } => "port_not_found",
+            Self::NodeMismatch { .. } => "node_mismatch",
+            Self::WrongDirection { .. } => "wrong_direction",
+            Self::MediaMismatch { .. } => "media_mismatch",
+            Self::LinkExists { .. } => "link_exists",
+            Self::Cycle { .. } => "cycle",
+        }
+    }
+}
+
+fn join_path(path: &[u32]) -> String {
+    let path: Vec<String> = path.iter().map(u32::to_string).collect();
+    path.join(" -> ")
+}
+
+/// Checks a link request against the current graph before it reaches
+/// PipeWire. Loops are refused unless `allow_cycle` is set, for setups that
+/// want feedback on purpose.
+pub fn check_link(
+    graph: &GraphManager,
+    output_node: u32,
+    output_port: u32,
+    input_node: u32,
+    input_port: u32,
+    allow_cycle: bool,
+) -> Result<(), LinkError> {
+    let mut ports = Vec::with_capacity(2);
+    for (port_id, node_id, direction) in [
+        (output_port, output_node, PortDirection::Output),
+        (input_port, input_node, PortDirection::Input),
+    ] {
+        let port = graph.get_port(port_id).ok_or(LinkError::PortNotFound { port: port_id })?;
+        if port.node_id != node_id {
+            return Err(LinkError::NodeMismatch {
+                port: port_id,
+                node: node_id,
+                actual_node: port.node_id,
+            });
+        }
+        if port.direction != direction {
+            return Err(LinkError::WrongDirection { port: port_id, expected: direction });
+        }
+        ports.push(port);
+    }
+
+    if let (Some(output), Some(input)) = (ports[0].properties.get(FORMAT_KEY), ports[1].properties.get(FORMAT_KEY)) {
+        if media(output) != media(input) {
+            return Err(LinkError::MediaMismatch {
+                output: output.clone(),
+                input: input.clone(),
+            });
+        }
+    }
+
+    if let Some(link) = graph.links_of_port(output_port).find(|l| l.input_port == input_port) {
+        return Err(LinkError::LinkExists { link: link.id });
+    }
+
+    if !allow_cycle {
+        if let Some(mut path) = graph.find_path(input_node, output_node) {
+            path.insert(0, output_node);
+            return Err(LinkError::Cycle { path });
+        }
+    }
+    Ok(())
+}
+
+/// The media kind at the end of a `format.dsp` value: `audio`, `midi`, ...
+/// Mono and stereo float audio link fine, so only the kind is compared.
+pub fn media(format: &str) -> &str {
+    format.rsplit(' ').next().unwrap_or(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::graph::{Link, Node, NodeGroups, NodeType, Port};
+    use std::collections::BTreeMap;
+
+    fn node(id: u32) -> Node {
+        Node {
+            id,
+            name: format!("node{}", id),
+            node_type: NodeType::Filter,
+            ports: Vec::new(),
+            properties: BTreeMap::new(),
+            groups: NodeGroups::default(),
+        }
+    }
+
+    fn port(id: u32, node_id: u32, direction: PortDirection, format: &str) -> Port {
+        Port {
+            id,
+            node_id,
+            name: format!("port{}", id),
+            direction,
+            channel: None,
+            properties: BTreeMap::from([(FORMAT_KEY.to_string(), format.to_string())]),
+        }
+    }
+
+    /// Filters 1 and 2, linked 1 -> 2, and a node 3 taking audio and MIDI.
+    fn graph() -> GraphManager {
+        let mut graph = GraphManager::new();
+        for id in 1..=3 {
+            graph.add_node(node(id));
+        }
+        graph.add_port(port(10, 1, PortDirection::Output, "32 bit float mono audio"));
+        graph.add_port(port(11, 1, PortDirection::Input, "32 bit float mono audio"));
+        graph.add_port(port(20, 2, PortDirection::Output, "32 bit float mono audio"));
+        graph.add_port(port(21, 2, PortDirection::Input, "32 bit float mono audio"));
+        graph.add_port(port(31, 3, PortDirection::Input, "8 bit raw midi"));
+        graph.add_port(port(32, 3, PortDirection::Input, "32 bit float audio"));
+        graph.add_link(Link { id: 100, output_node: 1, output_port: 10, input_node: 2, input_port: 21 });
+        graph
+    }
+
+    #[test]
+    fn accepts_matching_audio_ports() {
+        assert!(check_link(&graph(), 2, 20, 3, 32, false).is_ok());
+    }
+
+    #[test]
+    fn rejects_missing_port() {
+        let err = check_link(&graph(), 1, 99, 2, 21, false).unwrap_err();
+        assert!(matches!(err, LinkError::PortNotFound { port: 99 }));
+    }
+
+    #[test]
+    fn rejects_port_on_another_node() {
+        let err = check_link(&graph(), 2, 10, 3, 32, false).unwrap_err();
+        assert!(matches!(err, LinkError::NodeMismatch { port: 10, node: 2, actual_node: 1 }));
+    }
+
+    #[test]
+    fn rejects_output_to_output() {
+        let err = check_link(&graph(), 1, 10, 2, 20, false).unwrap_err();
+        assert!(matches!(err, LinkError::WrongDirection { port: 20, expected: PortDirection::Input }));
+    }
+
+    #[test]
+    fn rejects_media_mismatch() {
+        let err = check_link(&graph(), 2, 20, 3, 31, false).unwrap_err();
+        assert_eq!(err.code(), "media_mismatch");
+        assert_eq!(err.to_string(), "cannot link 32 bit float mono audio to 8 bit raw midi");
+    }
+
+    #[test]
+    fn rejects_duplicate_link() {
+        let err = check_link(&graph(), 1, 10, 2, 21, false).unwrap_err();
+        assert!(matches!(err, LinkError::LinkExists { link: 100 }));
+    }
+
+    #[test]
+    fn rejects_cycle_unless_allowed() {
+        let graph = graph();
+        let err = check_link(&graph, 2, 20, 1, 11, false).unwrap_err();
+        let LinkError::Cycle { path } = &err else {
+            panic!("expected a cycle, got {:?}", err);
+        };
+        assert_eq!(path, &[2, 1, 2]);
+        assert_eq!(err.to_string(), "link would create a feedback loop through nodes 2 -> 1 -> 2");
+
+        assert!(check_link(&graph, 2, 20, 1, 11, true).is_ok());
+    }
+}
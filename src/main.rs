@@ -17,20 +17,36 @@ mod graph;
 mod cli;
 mod systemd;
 
-use audio::controller::AudioController;
+use audio::boost::BoostStore;
+use audio::controller::{AudioController, VOLUME_EPSILON};
+use audio::meter::MeterManager;
 use audio::pipewire::{PipeWireHandler, PwEvent};
+use audio::player::{Player, PlayerConfig};
+use audio::recorder::{Recorder, RecorderConfig};
+use audio::rtp::RtpManager;
 use utils::broadcast::{EventBroadcaster, ServerEvent};
 use utils::logger::WsLogLayer;
 use utils::assets::{index_handler, static_handler};
+use graph::layout::LayoutStore;
 use graph::manager::GraphManager;
+use graph::presets::PresetStore;
+use graph::rules::RuleEngine;
 use models::graph::{Node, NodeType};
 
 #[derive(Clone)]
 pub struct AppState {
     pub audio: Arc<RwLock<AudioController>>,
+    pub boost: Arc<BoostStore>,
     pub graph: Arc<RwLock<GraphManager>>,
     pub broadcaster: Arc<EventBroadcaster>,
     pub pw_handler: Arc<PipeWireHandler>,
+    pub recorder: Arc<Recorder>,
+    pub player: Arc<Player>,
+    pub rtp: Arc<RtpManager>,
+    pub meters: Arc<MeterManager>,
+    pub presets: Arc<PresetStore>,
+    pub layout: Arc<LayoutStore>,
+    pub rules: Arc<RuleEngine>,
 }
 
 #[tokio::main]
@@ -65,6 +81,7 @@ async fn main() -> anyhow::Result<()> {
     let graph = Arc::new(RwLock::new(
         GraphManager::new()
     ));
+    let boost = Arc::new(BoostStore::load(cli.get_config_dir().join("boost.json"))?);
 
     // 2. Setup PipeWire Event Channel
     let (event_sender, event_receiver) = unbounded();
@@ -73,77 +90,205 @@ async fn main() -> anyhow::Result<()> {
     let pw_handler = Arc::new(PipeWireHandler::new(event_sender)?);
 
     // 4. Spawn Background Task to Process PipeWire Events
+    // Auto-connect rules run in the event loop as nodes, ports and links arrive
+    let rules = Arc::new(RuleEngine::load(cli.get_config_dir().join("rules.json"), pw_handler.clone())?);
     let audio_clone = audio.clone();
     let graph_clone = graph.clone();
     let broadcaster_clone = broadcaster.clone();
+    let rules_clone = rules.clone();
+    let boost_clone = boost.clone();
 
     tokio::task::spawn_blocking(move || {
         info!("Event listener started");
         while let Ok(event) = event_receiver.recv() {
-            match event {
-                PwEvent::DeviceAdded(device) => {
+            // Both locks are held until the event is sent so that a snapshot
+            // (taken under the read locks) always matches a single revision
+            let mut audio = audio_clone.write();
+            let mut graph = graph_clone.write();
+            let server_event = match event {
+                PwEvent::DeviceAdded(mut device) => {
                     info!("Device Added: {} ({})", device.name, device.id);
-                    audio_clone.write().add_device(device.clone());
-                    broadcaster_clone.send(ServerEvent::DeviceAdded(device.clone()));
+                    device.boost = boost_clone.enabled(&device.name);
+                    audio.add_device(device.clone());
 
                     let node = Node {
                         id: device.id,
-                        name: device.name,
+                        name: device.name.clone(),
                         node_type: match device.device_type {
                             models::device::DeviceType::Sink => NodeType::Device,
                             models::device::DeviceType::Source => NodeType::Device,
                         },
                         ports: Vec::new(),
+                        groups: graph::groups::node_groups(&device.properties),
+                        properties: device.properties.clone(),
                     };
-                    graph_clone.write().add_node(node);
+                    graph.add_node(node);
+                    ServerEvent::DeviceAdded(device)
                 }
                 PwEvent::DeviceRemoved(id) => {
                     info!("Device Removed: {}", id);
-                    audio_clone.write().remove_device(id);
-                    graph_clone.write().remove_node(id);
-                    broadcaster_clone.send(ServerEvent::DeviceRemoved(id));
+                    audio.remove_device(id);
+                    graph.remove_node(id);
+                    ServerEvent::DeviceRemoved(id)
                 }
+                // Node params repeat what was already applied; only report real changes
+                PwEvent::VolumeChanged(id, vol, None)
+                    if audio.volume(id).is_some_and(|v| (v - vol).abs() < VOLUME_EPSILON) => continue,
+                PwEvent::MuteChanged(id, muted)
+                    if audio.get_device(id).is_some_and(|d| d.muted == muted) => continue,
                 PwEvent::VolumeChanged(id, vol, timestamp) => {
                     info!("Volume Changed: {} -> {}", id, vol);
-                    broadcaster_clone.send(ServerEvent::VolumeChanged { id, volume: vol, timestamp });
+                    audio.set_volume(id, vol);
+                    ServerEvent::VolumeChanged { id, volume: vol, timestamp }
+                }
+                PwEvent::MuteChanged(id, muted) => {
+                    info!("Mute Changed: {} -> {}", id, muted);
+                    audio.set_mute(id, muted);
+                    ServerEvent::MuteChanged { id, muted }
+                }
+                PwEvent::StateChanged(id, state) => {
+                    audio.set_state(id, state);
+                    ServerEvent::StateChanged { id, state }
                 }
                 PwEvent::PortAdded(port) => {
-                    graph_clone.write().add_port(port.clone());
-                    broadcaster_clone.send(ServerEvent::PortAdded(port));
+                    graph.add_port(port.clone());
+                    ServerEvent::PortAdded(port)
                 }
                 PwEvent::PortRemoved(id) => {
-                    graph_clone.write().remove_port(id);
-                    broadcaster_clone.send(ServerEvent::PortRemoved(id));
+                    graph.remove_port(id);
+                    ServerEvent::PortRemoved(id)
                 }
                 PwEvent::LinkAdded(link) => {
-                    graph_clone.write().add_link(link.clone());
-                    broadcaster_clone.send(ServerEvent::LinkAdded(link));
+                    graph.add_link(link.clone());
+                    ServerEvent::LinkAdded(link)
                 }
                 PwEvent::LinkRemoved(id) => {
-                    graph_clone.write().remove_link(id);
-                    broadcaster_clone.send(ServerEvent::LinkRemoved(id));
+                    graph.remove_link(id);
+                    ServerEvent::LinkRemoved(id)
                 }
-            }
+                PwEvent::DefaultsChanged(defaults) => {
+                    info!("Defaults Changed: sink {:?}, source {:?}", defaults.audio_sink, defaults.audio_source);
+                    audio.set_defaults(defaults.clone());
+                    ServerEvent::DefaultsChanged(defaults)
+                }
+            };
+            rules_clone.handle(&audio, &graph, &server_event);
+            let revision = broadcaster_clone.send(server_event);
+            audio.set_revision(revision);
+            graph.set_revision(revision);
         }
         error!("Event listener loop ended unexpectedly");
     });
 
+    // 5. Recording Subsystem
+    let recorder = Arc::new(Recorder::new(
+        RecorderConfig {
+            dir: cli.get_recordings_dir(),
+            max_duration: std::time::Duration::from_secs(cli.max_recording_secs),
+            max_bytes: cli.max_recording_mb * 1024 * 1024,
+        },
+        broadcaster.clone(),
+        pw_handler.clone(),
+    ));
+
+    // 6. Playback Subsystem
+    let player = Arc::new(Player::new(
+        PlayerConfig {
+            sounds_dir: cli.get_sounds_dir(),
+            max_duration: std::time::Duration::from_secs(cli.max_playback_secs),
+        },
+        broadcaster.clone(),
+        pw_handler.clone(),
+    ));
+
+    // 7. Network Audio (RTP)
+    let rtp = Arc::new(RtpManager::new(broadcaster.clone()));
+
+    // 8. Level Meters
+    let meters = Arc::new(MeterManager::new(broadcaster.clone(), pw_handler.clone()));
+
+    // 9. Patchbay Presets
+    let presets = Arc::new(PresetStore::load(cli.get_config_dir().join("presets.json"))?);
+    if let Some(preset) = presets.enforced() {
+        info!("Enforcing preset {}", preset.name);
+    }
+    tokio::spawn(graph::presets::enforce(
+        presets.clone(),
+        graph.clone(),
+        pw_handler.clone(),
+        broadcaster.clone(),
+    ));
+
+    // 10. Patchbay Layout
+    let layout = Arc::new(LayoutStore::load(cli.get_config_dir().join("layout.json"))?);
+
     let state = AppState {
         audio,
+        boost,
         graph,
         broadcaster,
         pw_handler,
+        recorder,
+        player,
+        rtp: rtp.clone(),
+        meters,
+        presets,
+        layout,
+        rules,
     };
 
     let app = Router::new()
         .route("/", get(index_handler))
         .route("/*path", get(static_handler))
         .route("/api/devices", get(api::devices::list_devices))
-        .route("/api/device/:id/volume", axum::routing::post(api::devices::set_volume))
+        .route("/api/device/:id", get(api::devices::get_device))
+        .route("/api/device/:id/volume", get(api::devices::get_volume).post(api::devices::set_volume))
+        .route("/api/device/:id/boost", axum::routing::post(api::devices::set_boost))
+        .route("/api/device/:id/mute", axum::routing::post(api::devices::set_mute))
         .route("/api/graph", get(api::graph::get_graph))
+        .route("/api/graph.dot", get(api::graph::get_graph_dot))
+        .route("/api/graph.svg", get(api::graph::get_graph_svg))
+        .route("/api/layout", get(api::layout::get_layout).put(api::layout::update_layout))
+        .route("/api/graph/groups", get(api::graph::get_groups))
+        .route("/api/graph/collapsed", get(api::graph::get_collapsed_graph))
+        .route("/api/graph/nodes/:id/downstream", get(api::graph::get_downstream))
+        .route("/api/graph/nodes/:id/upstream", get(api::graph::get_upstream))
+        .route("/api/graph/nodes/:id/outputs", get(api::graph::get_hardware_outputs))
         .route("/api/link/create", axum::routing::post(api::graph::create_link))
         .route("/api/link/delete", axum::routing::post(api::graph::delete_link))
+        .route("/api/link/connect", axum::routing::post(api::graph::connect_nodes))
+        .route("/api/link/disconnect", axum::routing::post(api::graph::disconnect_nodes))
+        .route("/api/recordings", get(api::recordings::list_recordings))
+        .route("/api/recordings/:name", get(api::recordings::download_recording)
+            .delete(api::recordings::delete_recording))
+        .route("/api/recording/start", axum::routing::post(api::recordings::start_recording))
+        .route("/api/recording/:id/stop", axum::routing::post(api::recordings::stop_recording))
+        .route("/api/playback", get(api::playback::list_playback))
+        .route("/api/playback/start", axum::routing::post(api::playback::start_playback))
+        .route("/api/playback/:id/stop", axum::routing::post(api::playback::stop_playback))
+        .route("/api/listen/:id", get(api::listen::handler))
+        .route("/api/mic", get(api::mic::handler))
+        .route("/api/sounds/:name", axum::routing::put(api::playback::upload_sound)
+            .delete(api::playback::delete_sound)
+            .layer(axum::extract::DefaultBodyLimit::max(64 * 1024 * 1024)))
+        .route("/api/rtp", get(api::rtp::list_rtp).post(api::rtp::load_rtp))
+        .route("/api/rtp/:id", axum::routing::delete(api::rtp::unload_rtp))
+        .route("/api/presets", get(api::presets::list_presets).post(api::presets::save_preset))
+        .route("/api/presets/import", axum::routing::post(api::presets::import_preset))
+        .route("/api/presets/:name", axum::routing::delete(api::presets::delete_preset))
+        .route("/api/presets/:name/export", get(api::presets::export_preset))
+        .route("/api/presets/:name/rename", axum::routing::post(api::presets::rename_preset))
+        .route("/api/presets/:name/apply", axum::routing::post(api::presets::apply_preset))
+        .route("/api/presets/:name/dry-run", get(api::presets::dry_run_preset))
+        .route("/api/presets/:name/enforce", axum::routing::post(api::presets::enforce_preset)
+            .delete(api::presets::release_preset))
+        .route("/api/rules", get(api::rules::list_rules).post(api::rules::create_rule))
+        .route("/api/rules/log", get(api::rules::rule_log))
+        .route("/api/rules/:id", axum::routing::put(api::rules::update_rule)
+            .delete(api::rules::delete_rule))
+        .route("/api/metrics", get(api::metrics::get_metrics))
         .route("/ws", get(api::websocket::handler))
+        .route("/api/events", get(api::events::handler))
         .with_state(state);
 
     let addr = cli.get_listen_address();
@@ -161,7 +306,29 @@ async fn main() -> anyhow::Result<()> {
         info!("  To allow external access, use: --allow-external");
     }
     
-    axum::serve(listener, app).await?;
-    
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+
+    // RTP modules live in child processes that would otherwise outlive the server
+    rtp.unload_all();
+
     Ok(())
 }
+
+async fn shutdown_signal() {
+    let ctrl_c = tokio::signal::ctrl_c();
+    let mut term = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+        Ok(term) => term,
+        Err(e) => {
+            error!("Failed to install SIGTERM handler: {}", e);
+            let _ = ctrl_c.await;
+            return;
+        }
+    };
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = term.recv() => {},
+    }
+    info!("Shutting down");
+}
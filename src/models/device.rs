@@ -1,4 +1,6 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DeviceType {
@@ -6,7 +8,7 @@ pub enum DeviceType {
     Source,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DeviceState {
     Running,
     Suspended,
@@ -30,4 +32,156 @@ pub struct AudioDevice {
     pub channels: Vec<Channel>,
     pub muted: bool,
     pub base_volume: f32,
+    /// Whether volumes above 100% are allowed.
+    #[serde(default)]
+    pub boost: bool,
+    /// The node's global PipeWire properties (`application.name`, `media.class`, ...).
+    #[serde(default)]
+    pub properties: BTreeMap<String, String>,
+}
+
+/// Node names the session manager currently routes new streams to.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DefaultNodes {
+    pub audio_sink: Option<String>,
+    pub audio_source: Option<String>,
+}
+
+/// Decibel value reported for silence; a dB volume at or below it is silence.
+pub const MIN_DB: f32 = -96.0;
+
+/// How a volume number is read and reported. `cubic` is the percentage
+/// `wpctl` and pavucontrol show, and what volumes are stored and broadcast
+/// as; `linear` is the amplitude factor (the cube of the cubic volume) and
+/// `db` its gain in decibels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VolumeScale {
+    Linear,
+    #[default]
+    Cubic,
+    #[serde(alias = "dB")]
+    Db,
+}
+
+impl VolumeScale {
+    /// Expresses a cubic volume in this scale.
+    pub fn express(self, volume: f32) -> f32 {
+        match self {
+            Self::Linear => volume.max(0.0).powi(3),
+            Self::Cubic => volume,
+            Self::Db if volume <= 0.0 => MIN_DB,
+            Self::Db => (60.0 * volume.log10()).max(MIN_DB),
+        }
+    }
+
+    /// The cubic volume a value in this scale stands for.
+    pub fn to_cubic(self, value: f32) -> f32 {
+        match self {
+            Self::Linear => value.max(0.0).cbrt(),
+            Self::Cubic => value,
+            Self::Db if value <= MIN_DB => 0.0,
+            Self::Db => 10f32.powf(value / 60.0),
+        }
+    }
+}
+
+/// A requested volume: absolute (`0.5`, `"50%"`) or relative to the
+/// current one (`"+5%"`, `"-3 dB"`). Deserializes from a number or string.
+/// In the `db` scale a signed number is a level, see `in_scale`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VolumeChange {
+    Set(f32),
+    Step(f32),
+    Decibels(f32),
+}
+
+impl VolumeChange {
+    /// The change as meant in `scale`. dB levels are negative below unity,
+    /// so there `"-6"` sets −6 dB and steps take the suffix: `"-6 dB"`.
+    pub fn in_scale(self, scale: VolumeScale) -> Self {
+        match (self, scale) {
+            (Self::Step(level), VolumeScale::Db) => Self::Set(level),
+            (change, _) => change,
+        }
+    }
+}
+
+impl FromStr for VolumeChange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let relative = s.starts_with('+') || s.starts_with('-');
+        let number = |n: &str| {
+            n.trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .ok_or_else(|| format!("invalid volume {}", s))
+        };
+        let lower = s.to_ascii_lowercase();
+        if let Some(db) = lower.strip_suffix("db") {
+            if !relative {
+                return Err(format!("decibel volumes must be relative, e.g. -3 dB: {}", s));
+            }
+            return Ok(Self::Decibels(number(db)?));
+        }
+        let (value, scale) = match s.strip_suffix('%') {
+            Some(pct) => (pct, 100.0),
+            None => (s, 1.0),
+        };
+        let value = number(value)? / scale;
+        if relative {
+            Ok(Self::Step(value))
+        } else {
+            Ok(Self::Set(value))
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for VolumeChange {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Number(f32),
+            Text(String),
+        }
+        match Raw::deserialize(deserializer)? {
+            Raw::Number(n) if n.is_finite() => Ok(Self::Set(n)),
+            Raw::Number(n) => Err(serde::de::Error::custom(format!("invalid volume {}", n))),
+            Raw::Text(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> VolumeChange {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_absolute_and_relative() {
+        assert_eq!(parse("0.5"), VolumeChange::Set(0.5));
+        assert_eq!(parse("50%"), VolumeChange::Set(0.5));
+        assert_eq!(parse("+5%"), VolumeChange::Step(0.05));
+        assert_eq!(parse("-0.1"), VolumeChange::Step(-0.1));
+        assert_eq!(parse("-3 dB"), VolumeChange::Decibels(-3.0));
+        assert_eq!(parse("+1.5db"), VolumeChange::Decibels(1.5));
+        assert!("6 dB".parse::<VolumeChange>().is_err());
+        assert!("loud".parse::<VolumeChange>().is_err());
+    }
+
+    #[test]
+    fn signed_numbers_are_levels_in_db() {
+        assert_eq!(parse("-6").in_scale(VolumeScale::Db), VolumeChange::Set(-6.0));
+        assert_eq!(parse("+2").in_scale(VolumeScale::Db), VolumeChange::Set(2.0));
+        assert_eq!(parse("-6 dB").in_scale(VolumeScale::Db), VolumeChange::Decibels(-6.0));
+        assert_eq!(parse("-6").in_scale(VolumeScale::Cubic), VolumeChange::Step(-6.0));
+        assert_eq!(parse("-0.1").in_scale(VolumeScale::Linear), VolumeChange::Step(-0.1));
+    }
 }
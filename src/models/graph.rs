@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum NodeType {
@@ -7,7 +8,7 @@ pub enum NodeType {
     Filter,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PortDirection {
     Input,
     Output,
@@ -19,6 +20,11 @@ pub struct Port {
     pub node_id: u32,
     pub name: String,
     pub direction: PortDirection,
+    /// `audio.channel` position such as `FL`, `FR` or `MONO`.
+    #[serde(default)]
+    pub channel: Option<String>,
+    #[serde(default)]
+    pub properties: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +33,39 @@ pub struct Node {
     pub name: String,
     pub node_type: NodeType,
     pub ports: Vec<Port>,
+    #[serde(default)]
+    pub properties: BTreeMap<String, String>,
+    #[serde(default)]
+    pub groups: NodeGroups,
+}
+
+/// What a node can be grouped by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    /// The PipeWire client that created the node.
+    Client,
+    /// The device the node belongs to, e.g. one sound card's profiles.
+    Device,
+    /// The application, by name, across all its clients.
+    Application,
+}
+
+/// The groups a node belongs to; `None` where it has no such parent.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeGroups {
+    pub client: Option<u32>,
+    pub device: Option<u32>,
+    pub application: Option<String>,
+}
+
+/// Nodes sharing a client, device or application.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeGroup {
+    pub by: GroupBy,
+    pub key: String,
+    pub label: String,
+    pub nodes: Vec<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,3 +82,53 @@ pub struct AudioGraph {
     pub nodes: Vec<Node>,
     pub links: Vec<Link>,
 }
+
+/// Two ports paired by channel for a node-to-node connection.
+#[derive(Debug, Clone, Serialize)]
+pub struct PortPair {
+    pub output_port: u32,
+    pub input_port: u32,
+    pub output_channel: Option<String>,
+    pub input_channel: Option<String>,
+}
+
+/// Result of connecting two whole nodes.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NodeConnection {
+    pub created: Vec<PortPair>,
+    /// Pairs that were already linked.
+    pub existing: Vec<PortPair>,
+    /// Output ports left unconnected for want of a matching input.
+    pub unpaired: Vec<u32>,
+}
+
+/// Which way to follow links: towards sinks or towards sources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Flow {
+    Downstream,
+    Upstream,
+}
+
+/// Where a node's audio goes (or comes from).
+#[derive(Debug, Clone, Serialize)]
+pub struct Routes {
+    pub node: u32,
+    pub flow: Flow,
+    /// Every node reached, by id.
+    pub nodes: Vec<u32>,
+    /// Each route from `node` to a node that leads nowhere further, as node
+    /// ids in order.
+    pub paths: Vec<Vec<u32>>,
+    /// Set when there were too many routes to list them all.
+    pub truncated: bool,
+}
+
+/// A hardware sink an application's audio ends up in, and the shortest way
+/// there.
+#[derive(Debug, Clone, Serialize)]
+pub struct HardwareRoute {
+    pub node: u32,
+    pub name: String,
+    pub path: Vec<u32>,
+}
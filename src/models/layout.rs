@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Top-left corner of a node in the patchbay, in canvas pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Node positions keyed by node name. `auto` places the nodes present in
+/// the graph that have no saved position yet.
+#[derive(Debug, Clone, Serialize)]
+pub struct Layout {
+    pub saved: BTreeMap<String, Position>,
+    pub auto: BTreeMap<String, Position>,
+}
@@ -1,2 +1,8 @@
 pub mod device;
 pub mod graph;
+pub mod layout;
+pub mod playback;
+pub mod preset;
+pub mod recording;
+pub mod rtp;
+pub mod rule;
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PlaybackSignal {
+    Sine { frequency: f32 },
+    PinkNoise,
+    SpeakerSweep,
+    File { name: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaybackInfo {
+    pub id: u32,
+    pub node_id: u32,
+    pub node_name: String,
+    pub signal: PlaybackSignal,
+    pub level: f32,
+    pub timeout_ms: u64,
+}
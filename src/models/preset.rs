@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+
+/// A link identified by node and port names, which survive restarts and
+/// reconnects where registry ids don't.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PresetLink {
+    pub output_node: String,
+    pub output_port: String,
+    pub input_node: String,
+    pub input_port: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub links: Vec<PresetLink>,
+    pub saved: u64, // unix seconds
+    /// Whether the graph is continuously kept to exactly this preset. At
+    /// most one preset is enforced.
+    #[serde(default)]
+    pub enforced: bool,
+}
+
+/// A link an exclusive apply deletes.
+#[derive(Debug, Clone, Serialize)]
+pub struct RemovedLink {
+    pub id: u32,
+    #[serde(flatten)]
+    pub link: PresetLink,
+}
+
+/// What applying a preset did to each of its links.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PresetApply {
+    pub created: Vec<PresetLink>,
+    pub existing: Vec<PresetLink>,
+    /// Links whose nodes or ports aren't in the graph right now.
+    pub missing: Vec<PresetLink>,
+    /// Links not described by the preset; only filled in exclusive mode.
+    pub removed: Vec<RemovedLink>,
+}
@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RtpDirection {
+    Sink,   // sends local audio to the network
+    Source, // receives network audio as a local source
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RtpFormat {
+    #[default]
+    S16be,
+    S24be,
+    F32be,
+}
+
+impl RtpFormat {
+    pub fn as_spa(&self) -> &'static str {
+        match self {
+            RtpFormat::S16be => "S16BE",
+            RtpFormat::S24be => "S24BE",
+            RtpFormat::F32be => "F32BE",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RtpConfig {
+    pub direction: RtpDirection,
+    pub address: IpAddr, // destination for sinks, listen address for sources
+    pub port: u16,
+    #[serde(default)]
+    pub format: RtpFormat,
+    #[serde(default = "default_rate")]
+    pub rate: u32,
+    #[serde(default = "default_channels")]
+    pub channels: u32,
+    #[serde(default = "default_latency_ms")]
+    pub latency_ms: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+fn default_rate() -> u32 {
+    48000
+}
+
+fn default_channels() -> u32 {
+    2
+}
+
+fn default_latency_ms() -> u32 {
+    100
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RtpInstance {
+    pub id: u32,
+    #[serde(flatten)]
+    pub config: RtpConfig,
+    pub node_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<u32>,
+    /// Properties of the running node (`audio.rate`, `rtp.destination.ip`,
+    /// `node.latency`, ...), once it has shown up.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, String>,
+    pub pid: u32,
+    pub module_args: serde_json::Value,
+}
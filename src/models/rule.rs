@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+
+/// A condition on one property. Written as
+/// `{"key": "application.name", "glob": "OBS*"}` or with `"regex"`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PropertyMatch {
+    pub key: String,
+    #[serde(flatten)]
+    pub pattern: Pattern,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pattern {
+    Glob(String),
+    Regex(String),
+}
+
+/// Which side of the matched node a link action works on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkSide {
+    /// The matched node's outputs feed the other node's inputs.
+    #[default]
+    Output,
+    /// The other node's outputs feed the matched node's inputs.
+    Input,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleAction {
+    /// Links the matched node's ports (those matching `ports`) to the first
+    /// node matching `target`, pairing ports in order.
+    Link {
+        target: Vec<PropertyMatch>,
+        #[serde(default)]
+        side: LinkSide,
+        #[serde(default)]
+        ports: Vec<PropertyMatch>,
+    },
+    /// Removes links on the matched node's ports, optionally only those to
+    /// nodes matching `target`.
+    Unlink {
+        #[serde(default)]
+        target: Option<Vec<PropertyMatch>>,
+        #[serde(default)]
+        side: LinkSide,
+        #[serde(default)]
+        ports: Vec<PropertyMatch>,
+    },
+    Volume {
+        volume: f32,
+    },
+    Mute {
+        muted: bool,
+    },
+    /// Asks the session manager to route the stream to the first node
+    /// matching `target` (sets its `target.object` metadata).
+    Move {
+        target: Vec<PropertyMatch>,
+    },
+}
+
+/// A rule as submitted through the API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleSpec {
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Conditions on the node's properties; all must hold.
+    pub node: Vec<PropertyMatch>,
+    pub actions: Vec<RuleAction>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub id: u32,
+    #[serde(flatten)]
+    pub spec: RuleSpec,
+}
+
+/// An entry in the log of rules that fired.
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleFiring {
+    pub time: u64, // unix seconds
+    pub rule_id: u32,
+    pub rule_name: String,
+    pub node_id: u32,
+    pub node_name: String,
+    /// What the rule did, e.g. `link 57 -> 81`.
+    pub effects: Vec<String>,
+}
@@ -1,8 +1,19 @@
-use crate::models::device::AudioDevice;
-use crate::models::graph::{Link, Port};
-use serde::Serialize;
+use crate::models::device::{AudioDevice, DefaultNodes, DeviceState};
+use crate::models::graph::{AudioGraph, Link, Port};
+use crate::models::layout::Position;
+use crate::models::playback::PlaybackInfo;
+use crate::models::recording::RecordingInfo;
+use crate::models::rtp::RtpInstance;
+use parking_lot::Mutex;
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::broadcast;
 
+// State-changing events kept for clients resyncing after a reconnect or lag
+const HISTORY_LEN: usize = 1024;
+
 #[derive(Clone, Debug, Serialize)]
 #[serde(tag = "type", content = "data")]
 pub enum ServerEvent {
@@ -14,28 +25,230 @@ pub enum ServerEvent {
         #[serde(skip_serializing_if = "Option::is_none")]
         timestamp: Option<u64>,
     },
+    MuteChanged {
+        id: u32,
+        muted: bool,
+    },
+    /// Boost was enabled or disabled; without it volumes stop at 100%.
+    BoostChanged {
+        id: u32,
+        boost: bool,
+    },
+    StateChanged {
+        id: u32,
+        state: DeviceState,
+    },
     PortAdded(Port),
     PortRemoved(u32),
     LinkAdded(Link),
     LinkRemoved(u32),
-    Log(String),
+    DefaultsChanged(DefaultNodes),
+    RecordingStarted(RecordingInfo),
+    RecordingProgress {
+        id: u32,
+        elapsed_ms: u64,
+        bytes: u64,
+    },
+    RecordingStopped {
+        id: u32,
+        file: String,
+        reason: String,
+    },
+    PlaybackStarted(PlaybackInfo),
+    PlaybackStopped {
+        id: u32,
+        reason: String,
+    },
+    RtpLoaded(RtpInstance),
+    RtpUnloaded {
+        id: u32,
+        reason: String,
+    },
+    Meter {
+        id: u32,
+        peaks: Vec<f32>, // linear peak per channel since the last update
+    },
+    Log(LogLine),
+    /// Saved node positions that changed; `null` for one that was cleared.
+    LayoutChanged(BTreeMap<String, Option<Position>>),
+    /// Full state sent to a single client instead of deltas; never broadcast.
+    Snapshot(StateSnapshot),
+}
+
+impl ServerEvent {
+    /// Whether the event changes device or graph state, i.e. whether missing
+    /// it leaves a client with a wrong picture rather than a stale log line.
+    pub fn is_state(&self) -> bool {
+        matches!(
+            self,
+            ServerEvent::DeviceAdded(_)
+                | ServerEvent::DeviceRemoved(_)
+                | ServerEvent::VolumeChanged { .. }
+                | ServerEvent::MuteChanged { .. }
+                | ServerEvent::BoostChanged { .. }
+                | ServerEvent::StateChanged { .. }
+                | ServerEvent::PortAdded(_)
+                | ServerEvent::PortRemoved(_)
+                | ServerEvent::LinkAdded(_)
+                | ServerEvent::LinkRemoved(_)
+                | ServerEvent::DefaultsChanged(_)
+        )
+    }
+}
+
+/// A formatted log line. Serialized as the plain string; the level is kept
+/// for per-client filtering.
+#[derive(Clone, Debug)]
+pub struct LogLine {
+    pub level: tracing::Level,
+    pub text: String,
+}
+
+impl Serialize for LogLine {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.text)
+    }
 }
 
+#[derive(Clone, Debug, Serialize)]
+pub struct StateSnapshot {
+    pub server: ServerInfo,
+    pub devices: Vec<AudioDevice>,
+    pub graph: Arc<AudioGraph>,
+    pub defaults: DefaultNodes,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ServerInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+}
+
+impl ServerInfo {
+    pub fn current() -> Self {
+        Self {
+            name: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+        }
+    }
+}
+
+/// An event as sent on the wire: `{"type", "data", "revision"}`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Stamped {
+    pub revision: u64,
+    #[serde(flatten)]
+    pub event: ServerEvent,
+}
+
+/// What a (re)connecting client needs before following live events.
+pub enum Catchup {
+    /// Every state event after the client's revision, in order.
+    Deltas(Vec<Stamped>),
+    /// History doesn't reach back far enough; send state as of this revision.
+    Snapshot(u64),
+}
+
+struct History {
+    revision: u64,
+    events: VecDeque<Stamped>,
+    evicted: u64, // revision of the newest event dropped from `events`
+}
+
+/// Stamps events with a global revision and fans them out. Meter frames go
+/// out on a channel of their own, unstamped, so their rate neither bumps the
+/// revision nor crowds state events out of the main channel.
+///
+/// To stay consistent with a snapshot, callers that mutate `AudioController`
+/// or `GraphManager` must send the matching event while still holding the
+/// write lock, and snapshot readers hold the read locks across
+/// `subscribe_since`. The history lock is always taken last.
 pub struct EventBroadcaster {
-    sender: broadcast::Sender<ServerEvent>,
+    sender: broadcast::Sender<Stamped>,
+    meters: broadcast::Sender<ServerEvent>,
+    history: Mutex<History>,
 }
 
 impl EventBroadcaster {
     pub fn new() -> Self {
         let (sender, _) = broadcast::channel(1000); // Increased buffer for logs
-        Self { sender }
+        let (meters, _) = broadcast::channel(256);
+        // Seeded from the clock so a revision from before a restart is never
+        // mistaken for one in this run's history
+        let start = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64 * 1000)
+            .unwrap_or(0);
+        Self {
+            sender,
+            meters,
+            history: Mutex::new(History {
+                revision: start,
+                events: VecDeque::with_capacity(HISTORY_LEN),
+                evicted: start,
+            }),
+        }
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.history.lock().revision
+    }
+
+    /// Subscribes and works out, atomically with the subscription, what the
+    /// client missed since `since`. The receiver yields only later events.
+    pub fn subscribe_since(&self, since: u64) -> (broadcast::Receiver<Stamped>, Catchup) {
+        let history = self.history.lock();
+        let receiver = self.sender.subscribe();
+
+        let catchup = if (history.evicted..=history.revision).contains(&since) {
+            Catchup::Deltas(
+                history
+                    .events
+                    .iter()
+                    .filter(|e| e.revision > since)
+                    .cloned()
+                    .collect(),
+            )
+        } else {
+            Catchup::Snapshot(history.revision)
+        };
+        (receiver, catchup)
+    }
+
+    /// Subscribes and returns the revision the receiver starts after.
+    pub fn subscribe_at(&self) -> (broadcast::Receiver<Stamped>, u64) {
+        let history = self.history.lock();
+        (self.sender.subscribe(), history.revision)
+    }
+
+    pub fn subscribe_meters(&self) -> broadcast::Receiver<ServerEvent> {
+        self.meters.subscribe()
     }
 
-    pub fn subscribe(&self) -> broadcast::Receiver<ServerEvent> {
-        self.sender.subscribe()
+    /// Sends a meter frame. Frames are not stamped or kept; a client that
+    /// falls behind just misses some.
+    pub fn send_meter(&self, id: u32, peaks: Vec<f32>) {
+        let _ = self.meters.send(ServerEvent::Meter { id, peaks });
     }
 
-    pub fn send(&self, event: ServerEvent) {
-        let _ = self.sender.send(event);
+    /// Sends the event and returns the revision it was stamped with.
+    pub fn send(&self, event: ServerEvent) -> u64 {
+        let mut history = self.history.lock();
+        history.revision += 1;
+        let stamped = Stamped {
+            revision: history.revision,
+            event,
+        };
+        if stamped.event.is_state() {
+            if history.events.len() == HISTORY_LEN {
+                if let Some(oldest) = history.events.pop_front() {
+                    history.evicted = oldest.revision;
+                }
+            }
+            history.events.push_back(stamped.clone());
+        }
+        let revision = stamped.revision;
+        let _ = self.sender.send(stamped);
+        revision
     }
 }
@@ -0,0 +1,110 @@
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileEntry {
+    pub name: String,
+    pub size: u64,
+    pub modified: u64, // unix seconds
+}
+
+/// True for plain file names with one of `extensions`; rejects anything that
+/// could point outside the directory it is joined onto.
+pub fn is_safe_name(name: &str, extensions: &[&str]) -> bool {
+    !name.starts_with('.')
+        && !name.contains('/')
+        && !name.contains('\\')
+        && extensions
+            .iter()
+            .any(|ext| name.len() > ext.len() + 1 && name.ends_with(&format!(".{}", ext)))
+}
+
+/// Resolves an API-supplied file name to an existing file inside `dir`.
+pub fn resolve(dir: &Path, name: &str, extensions: &[&str]) -> Option<PathBuf> {
+    if !is_safe_name(name, extensions) {
+        return None;
+    }
+    let path = dir.join(name);
+    path.is_file().then_some(path)
+}
+
+/// Lists files in `dir` with one of `extensions`, newest first. A missing
+/// directory is treated as empty.
+pub fn list(dir: &Path, extensions: &[&str]) -> Result<Vec<FileEntry>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut files = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !is_safe_name(&name, extensions) {
+            continue;
+        }
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if !meta.is_file() {
+            continue;
+        }
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        files.push(FileEntry {
+            name,
+            size: meta.len(),
+            modified,
+        });
+    }
+    files.sort_by_key(|f| std::cmp::Reverse(f.modified));
+    Ok(files)
+}
+
+/// Replaces everything but ASCII alphanumerics, `-`, `_` and `.` so the
+/// result can be used as part of a file name.
+pub fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect::<String>()
+        .trim_start_matches('.')
+        .to_string()
+}
+
+/// Reads a JSON file written by `write_json`; a missing file reads as the
+/// default value, so stores start out empty.
+pub fn read_json<T: DeserializeOwned + Default>(path: &Path) -> Result<T> {
+    match fs::read(path) {
+        Ok(data) => serde_json::from_slice(&data).with_context(|| format!("Invalid JSON in {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Writes `value` as pretty JSON, creating the parent directory. The file is
+/// written aside and renamed over, so a crash never leaves it truncated.
+pub fn write_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let data = serde_json::to_vec_pretty(value)?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, data).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -1,4 +1,4 @@
-use crate::utils::broadcast::{EventBroadcaster, ServerEvent};
+use crate::utils::broadcast::{EventBroadcaster, LogLine, ServerEvent};
 use std::sync::Arc;
 use tracing::Subscriber;
 use tracing_subscriber::Layer;
@@ -34,9 +34,9 @@ impl<S: Subscriber> Layer<S> for WsLogLayer {
         event.record(&mut visitor);
 
         if !visitor.message.is_empty() {
-            let level = event.metadata().level().to_string();
-            let log_line = format!("[{}] [{}] {}", level, target, visitor.message);
-            self.broadcaster.send(ServerEvent::Log(log_line));
+            let level = *event.metadata().level();
+            let text = format!("[{}] [{}] {}", level, target, visitor.message);
+            self.broadcaster.send(ServerEvent::Log(LogLine { level, text }));
         }
     }
 }
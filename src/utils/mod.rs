@@ -1,3 +1,5 @@
 pub mod broadcast;
 pub mod logger;
 pub mod assets;
+pub mod files;
+pub mod topics;
@@ -0,0 +1,148 @@
+use crate::utils::broadcast::ServerEvent;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::str::FromStr;
+use tracing::Level;
+
+/// Groups of events a client can opt into. Meter updates are subscribed per
+/// node rather than as a topic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Topic {
+    Devices,
+    Volume,
+    Graph,
+    Media, // recording, playback and RTP
+    Logs,
+}
+
+impl Topic {
+    pub const ALL: [Topic; 5] = [Topic::Devices, Topic::Volume, Topic::Graph, Topic::Media, Topic::Logs];
+
+    /// Whether the topic carries state that a snapshot brings up to date.
+    pub fn is_state(self) -> bool {
+        matches!(self, Topic::Devices | Topic::Volume | Topic::Graph)
+    }
+}
+
+impl FromStr for Topic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "devices" => Ok(Topic::Devices),
+            "volume" => Ok(Topic::Volume),
+            "graph" => Ok(Topic::Graph),
+            "media" => Ok(Topic::Media),
+            "logs" => Ok(Topic::Logs),
+            other => Err(format!("unknown topic {}", other)),
+        }
+    }
+}
+
+/// What a single connection wants to receive.
+#[derive(Debug, Clone)]
+pub struct TopicFilter {
+    pub topics: BTreeSet<Topic>,
+    /// Most verbose log level delivered under `Topic::Logs`.
+    pub log_level: Level,
+    /// Nodes whose peak meters are delivered.
+    pub meters: BTreeSet<u32>,
+}
+
+impl Default for TopicFilter {
+    /// Everything but meters, matching what clients received before
+    /// subscriptions existed.
+    fn default() -> Self {
+        Self {
+            topics: Topic::ALL.into_iter().collect(),
+            log_level: Level::TRACE,
+            meters: BTreeSet::new(),
+        }
+    }
+}
+
+impl TopicFilter {
+    pub fn none() -> Self {
+        Self {
+            topics: BTreeSet::new(),
+            log_level: Level::TRACE,
+            meters: BTreeSet::new(),
+        }
+    }
+
+    pub fn has_state(&self) -> bool {
+        self.topics.iter().any(|t| t.is_state())
+    }
+
+    pub fn matches(&self, event: &ServerEvent) -> bool {
+        let topic = match event {
+            // The device list and the patchbay both show nodes
+            ServerEvent::DeviceAdded(_) | ServerEvent::DeviceRemoved(_) => {
+                return self.topics.contains(&Topic::Devices) || self.topics.contains(&Topic::Graph);
+            }
+            ServerEvent::DefaultsChanged(_) | ServerEvent::StateChanged { .. } => Topic::Devices,
+            ServerEvent::VolumeChanged { .. }
+            | ServerEvent::MuteChanged { .. }
+            | ServerEvent::BoostChanged { .. } => Topic::Volume,
+            ServerEvent::PortAdded(_)
+            | ServerEvent::PortRemoved(_)
+            | ServerEvent::LinkAdded(_)
+            | ServerEvent::LinkRemoved(_)
+            | ServerEvent::LayoutChanged(_) => Topic::Graph,
+            ServerEvent::RecordingStarted(_)
+            | ServerEvent::RecordingProgress { .. }
+            | ServerEvent::RecordingStopped { .. }
+            | ServerEvent::PlaybackStarted(_)
+            | ServerEvent::PlaybackStopped { .. }
+            | ServerEvent::RtpLoaded(_)
+            | ServerEvent::RtpUnloaded { .. } => Topic::Media,
+            ServerEvent::Meter { id, .. } => return self.meters.contains(id),
+            // Levels order from ERROR (least verbose) up to TRACE
+            ServerEvent::Log(line) => {
+                return self.topics.contains(&Topic::Logs) && line.level <= self.log_level;
+            }
+            // A snapshot only brings state topics up to date
+            ServerEvent::Snapshot(_) => return self.has_state(),
+        };
+        self.topics.contains(&topic)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::graph::AudioGraph;
+    use crate::utils::broadcast::{LogLine, ServerInfo, StateSnapshot};
+    use std::sync::Arc;
+
+    fn filter(topics: &[Topic]) -> TopicFilter {
+        TopicFilter {
+            topics: topics.iter().copied().collect(),
+            ..TopicFilter::none()
+        }
+    }
+
+    #[test]
+    fn snapshots_only_with_state_topics() {
+        let snapshot = ServerEvent::Snapshot(StateSnapshot {
+            server: ServerInfo::current(),
+            devices: Vec::new(),
+            graph: Arc::new(AudioGraph { nodes: Vec::new(), links: Vec::new() }),
+            defaults: Default::default(),
+        });
+        assert!(TopicFilter::default().matches(&snapshot));
+        assert!(filter(&[Topic::Volume]).matches(&snapshot));
+        assert!(!filter(&[Topic::Logs]).matches(&snapshot));
+        assert!(!filter(&[Topic::Logs, Topic::Media]).matches(&snapshot));
+    }
+
+    #[test]
+    fn logs_respect_the_level() {
+        let line = |level| ServerEvent::Log(LogLine { level, text: String::new() });
+        let warnings = TopicFilter { log_level: Level::WARN, ..filter(&[Topic::Logs]) };
+        assert!(warnings.matches(&line(Level::ERROR)));
+        assert!(!warnings.matches(&line(Level::DEBUG)));
+        assert!(!filter(&[Topic::Graph]).matches(&line(Level::ERROR)));
+    }
+}
@@ -30,6 +30,7 @@ export class ReteGraph extends HTMLElement {
         this.graph = { nodes: [], links: [] };
         this.nodeMap = new Map();
         this.nodePositions = new Map();
+        this.layout = { saved: {}, auto: {} }; // shared positions by node name
         this.isUpdating = false;
         this.pickedConnection = null;
     }
@@ -227,6 +228,20 @@ export class ReteGraph extends HTMLElement {
                 }));
             }
             
+            if (context.type === 'nodedragged') {
+                const node = this.editor.getNode(context.data.id);
+                const view = this.area.nodeViews.get(context.data.id);
+                if (node && view) {
+                    const { x, y } = view.position;
+                    this.layout.saved[node.data.name] = { x, y };
+                    this.dispatchEvent(new CustomEvent('node-moved', {
+                        detail: { name: node.data.name, x, y },
+                        bubbles: true,
+                        composed: true
+                    }));
+                }
+            }
+
             if (context.type === 'connectionremoved') {
                 const conn = context.data;
                 if (conn.pwLinkId) {
@@ -281,6 +296,34 @@ export class ReteGraph extends HTMLElement {
         return null;
     }
 
+    setLayout(layout) {
+        this.layout = { saved: layout?.saved ?? {}, auto: layout?.auto ?? {} };
+    }
+
+    layoutPosition(nodeData) {
+        return this.layout.saved[nodeData.name] ?? this.layout.auto[nodeData.name];
+    }
+
+    // Moves nodes another client repositioned; null means the position was cleared
+    async applyLayout(changes) {
+        if (!this.editor || !this.area) return;
+        for (const [name, position] of Object.entries(changes)) {
+            if (position) {
+                this.layout.saved[name] = position;
+            } else {
+                delete this.layout.saved[name];
+                continue;
+            }
+            for (const node of this.editor.getNodes()) {
+                if (node.data.name !== name) continue;
+                const view = this.area.nodeViews.get(node.id);
+                if (view && (view.position.x !== position.x || view.position.y !== position.y)) {
+                    await this.area.translate(node.id, position);
+                }
+            }
+        }
+    }
+
     async setGraph(graph) {
         this.graph = graph;
         if (this.editor && this.area) {
@@ -348,11 +391,12 @@ export class ReteGraph extends HTMLElement {
                 }
             }
             
-            if (newNodes.length > 0 && this.nodePositions.size === 0) {
+            const hasLayout = Object.keys(this.layout.saved).length + Object.keys(this.layout.auto).length > 0;
+            if (newNodes.length > 0 && this.nodePositions.size === 0 && !hasLayout) {
                 this.layoutNodes(newNodes);
             } else {
                 for (const { node, nodeData } of newNodes) {
-                    const savedPos = this.nodePositions.get(nodeData.id);
+                    const savedPos = this.layoutPosition(nodeData) ?? this.nodePositions.get(nodeData.id);
                     const x = savedPos?.x ?? (index * 300 + 50);
                     const y = savedPos?.y ?? 50;
                     await this.area.translate(node.id, { x, y });
@@ -2,17 +2,26 @@ export class ApiClient {
     constructor() {
         this.listeners = new Map();
         this.ws = null;
+        this.revision = null; // last revision received, for resync on reconnect
+        this.nextRequestId = 1;
+        this.pending = new Map(); // request id -> { resolve, reject }
         this.connect();
     }
 
     connect() {
         const proto = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
-        this.ws = new WebSocket(`${proto}//${window.location.host}/ws`);
+        const since = this.revision !== null ? `?since=${this.revision}` : '';
+        this.ws = new WebSocket(`${proto}//${window.location.host}/ws${since}`);
 
         this.ws.onmessage = (event) => {
             try {
                 const msg = JSON.parse(event.data);
+                if (msg.type === 'Response') {
+                    this.settle(msg);
+                    return;
+                }
                 console.log('WS Message received:', msg.type);
+                if (msg.revision !== undefined) this.revision = msg.revision;
                 this.emit(msg.type, msg.data);
             } catch (e) {
                 console.error('Failed to parse WS message:', e);
@@ -20,11 +29,48 @@ export class ApiClient {
         };
 
         this.ws.onclose = () => {
+            for (const { reject } of this.pending.values()) {
+                reject(new Error('WebSocket closed'));
+            }
+            this.pending.clear();
             console.log('WS closed, reconnecting in 3s...');
             setTimeout(() => this.connect(), 3000);
         };
     }
 
+    // Sends a command over the WebSocket and resolves with its result
+    request(method, params = {}) {
+        return new Promise((resolve, reject) => {
+            if (this.ws?.readyState !== WebSocket.OPEN) {
+                reject(new Error('WebSocket not connected'));
+                return;
+            }
+            const id = this.nextRequestId++;
+            this.pending.set(id, { resolve, reject });
+            this.ws.send(JSON.stringify({ id, method, params }));
+        });
+    }
+
+    // e.g. subscribe({ topics: ['volume'], log_level: 'warn', meters: [42] })
+    subscribe(params = {}) {
+        return this.request('subscribe', params);
+    }
+
+    unsubscribe(params = {}) {
+        return this.request('unsubscribe', params);
+    }
+
+    settle(msg) {
+        const pending = this.pending.get(msg.id);
+        if (!pending) return;
+        this.pending.delete(msg.id);
+        if (msg.error) {
+            pending.reject(new Error(`${msg.error.code}: ${msg.error.message}`));
+        } else {
+            pending.resolve(msg.result);
+        }
+    }
+
     on(event, callback) {
         if (!this.listeners.has(event)) {
             this.listeners.set(event, new Set());
@@ -48,20 +94,140 @@ export class ApiClient {
         return res.json();
     }
 
-    async setVolume(id, volume, timestamp = null) {
+    // by is 'client', 'device' or 'application'
+    async getGroups(by) {
+        const res = await fetch(`/api/graph/groups?by=${by}`);
+        if (!res.ok) throw new Error(`Failed to get groups (${res.status})`);
+        return res.json();
+    }
+
+    // The graph with groups merged into single nodes; all groups unless keys are given
+    async getCollapsedGraph(by, keys = null) {
+        const query = keys ? `&groups=${encodeURIComponent(keys.join(','))}` : '';
+        const res = await fetch(`/api/graph/collapsed?by=${by}${query}`);
+        if (!res.ok) throw new Error(`Failed to get collapsed graph (${res.status})`);
+        return res.json();
+    }
+
+    // Resolves with { saved, auto }: node positions keyed by node name
+    async getLayout() {
+        const res = await fetch('/api/layout');
+        return res.json();
+    }
+
+    // positions maps node names to { x, y }, or null to forget a position
+    async saveLayout(positions) {
+        const res = await fetch('/api/layout', {
+            method: 'PUT',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify(positions)
+        });
+        if (!res.ok) throw new Error(`Failed to save layout (${res.status})`);
+        return res.json();
+    }
+
+    // flow is 'downstream' or 'upstream'; resolves with { nodes, paths, truncated }
+    async getRoutes(nodeId, flow = 'downstream') {
+        const res = await fetch(`/api/graph/nodes/${nodeId}/${flow}`);
+        if (!res.ok) throw new Error(`Failed to get routes (${res.status})`);
+        return res.json();
+    }
+
+    // Hardware sinks a node's audio ends up in
+    async getHardwareOutputs(nodeId) {
+        const res = await fetch(`/api/graph/nodes/${nodeId}/outputs`);
+        if (!res.ok) throw new Error(`Failed to get outputs (${res.status})`);
+        return res.json();
+    }
+
+    // scale is 'cubic' (the wpctl/pavucontrol percentage), 'linear' or 'db';
+    // resolves with { volume, max, scale }
+    async getVolume(id, scale = 'cubic') {
+        const res = await fetch(`/api/device/${id}/volume?scale=${scale}`);
+        if (!res.ok) throw new Error(`Failed to get volume (${res.status})`);
+        return res.json();
+    }
+
+    // volume is 0.5, '50%', or relative such as '+5%' or '-3 dB', read in scale
+    async setVolume(id, volume, timestamp = null, scale = 'cubic') {
+        // Fader moves go over the open socket; HTTP is the fallback
+        if (this.ws?.readyState === WebSocket.OPEN) {
+            return this.request('set_volume', { id, volume, scale, timestamp });
+        }
         await fetch(`/api/device/${id}/volume`, {
             method: 'POST',
             headers: { 'Content-Type': 'application/json' },
-            body: JSON.stringify({ volume, timestamp })
+            body: JSON.stringify({ volume, scale, timestamp })
+        });
+    }
+
+    // Allows volumes above 100% for a device; resolves with { boost }
+    async setBoost(id, enabled) {
+        const res = await fetch(`/api/device/${id}/boost`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ enabled })
         });
+        if (!res.ok) throw new Error(`Failed to set boost (${res.status})`);
+        return res.json();
     }
 
+    // Omit muted to toggle; resolves with { muted }
+    async setMute(id, muted = null) {
+        if (this.ws?.readyState === WebSocket.OPEN) {
+            return this.request('set_mute', muted === null ? { id } : { id, muted });
+        }
+        const res = await fetch(`/api/device/${id}/mute`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify(muted === null ? {} : { muted })
+        });
+        if (!res.ok) throw new Error(`Failed to set mute (${res.status})`);
+        return res.json();
+    }
+
+    // Pass `allow_cycle: true` in linkData to create a feedback loop on purpose
     async createLink(linkData) {
-        await fetch('/api/link/create', {
+        const res = await fetch('/api/link/create', {
             method: 'POST',
             headers: { 'Content-Type': 'application/json' },
             body: JSON.stringify(linkData)
         });
+        if (!res.ok) {
+            const body = await res.json().catch(() => null);
+            const err = new Error(body?.message ?? `Failed to create link (${res.status})`);
+            err.code = body?.error;
+            err.details = body;
+            throw err;
+        }
+    }
+
+    // Links two whole nodes, pairing ports by channel; resolves with
+    // { created, existing } port pairs
+    async connectNodes(outputNode, inputNode, { allowCycle = false } = {}) {
+        const res = await fetch('/api/link/connect', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ output_node: outputNode, input_node: inputNode, allow_cycle: allowCycle })
+        });
+        const body = await res.json().catch(() => null);
+        if (!res.ok) {
+            const err = new Error(body?.message ?? `Failed to connect nodes (${res.status})`);
+            err.code = body?.error;
+            err.details = body;
+            throw err;
+        }
+        return body;
+    }
+
+    // Removes every link between two nodes; resolves with the removed links
+    async disconnectNodes(outputNode, inputNode) {
+        const res = await fetch('/api/link/disconnect', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ output_node: outputNode, input_node: inputNode })
+        });
+        return res.json();
     }
 
     async deleteLink(linkId) {
@@ -71,4 +237,164 @@ export class ApiClient {
             body: JSON.stringify({ linkId })
         });
     }
+
+    async getRecordings() {
+        const res = await fetch('/api/recordings');
+        return res.json();
+    }
+
+    async startRecording(nodeId, format = 'wav') {
+        const res = await fetch('/api/recording/start', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ node_id: nodeId, format })
+        });
+        return res.json();
+    }
+
+    async stopRecording(id) {
+        await fetch(`/api/recording/${id}/stop`, { method: 'POST' });
+    }
+
+    async deleteRecording(name) {
+        await fetch(`/api/recordings/${encodeURIComponent(name)}`, { method: 'DELETE' });
+    }
+
+    async getPlayback() {
+        const res = await fetch('/api/playback');
+        return res.json();
+    }
+
+    async startPlayback(nodeId, signal, { level, durationSecs, channels } = {}) {
+        const res = await fetch('/api/playback/start', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ node_id: nodeId, signal, level, duration_secs: durationSecs, channels })
+        });
+        return res.json();
+    }
+
+    async stopPlayback(id) {
+        await fetch(`/api/playback/${id}/stop`, { method: 'POST' });
+    }
+
+    async uploadSound(name, file) {
+        const res = await fetch(`/api/sounds/${encodeURIComponent(name)}`, {
+            method: 'PUT',
+            body: file
+        });
+        return res.json();
+    }
+
+    async deleteSound(name) {
+        await fetch(`/api/sounds/${encodeURIComponent(name)}`, { method: 'DELETE' });
+    }
+
+    async getRtp() {
+        const res = await fetch('/api/rtp');
+        return res.json();
+    }
+
+    async loadRtp(config) {
+        const res = await fetch('/api/rtp', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify(config)
+        });
+        if (!res.ok) throw new Error(`Failed to load RTP module (${res.status})`);
+        return res.json();
+    }
+
+    async unloadRtp(id) {
+        await fetch(`/api/rtp/${id}`, { method: 'DELETE' });
+    }
+
+    async getPresets() {
+        const res = await fetch('/api/presets');
+        return res.json();
+    }
+
+    async savePreset(name) {
+        const res = await fetch('/api/presets', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ name })
+        });
+        if (!res.ok) throw new Error(`Failed to save preset (${res.status})`);
+        return res.json();
+    }
+
+    async renamePreset(name, newName) {
+        const res = await fetch(`/api/presets/${encodeURIComponent(name)}/rename`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ name: newName })
+        });
+        if (!res.ok) throw new Error(`Failed to rename preset (${res.status})`);
+    }
+
+    async deletePreset(name) {
+        await fetch(`/api/presets/${encodeURIComponent(name)}`, { method: 'DELETE' });
+    }
+
+    async applyPreset(name, { exclusive = false } = {}) {
+        const res = await fetch(`/api/presets/${encodeURIComponent(name)}/apply?exclusive=${exclusive}`, { method: 'POST' });
+        if (!res.ok) throw new Error(`Failed to apply preset (${res.status})`);
+        return res.json();
+    }
+
+    // Reports the links applyPreset would create and remove
+    async dryRunPreset(name, { exclusive = false } = {}) {
+        const res = await fetch(`/api/presets/${encodeURIComponent(name)}/dry-run?exclusive=${exclusive}`);
+        if (!res.ok) throw new Error(`Failed to check preset (${res.status})`);
+        return res.json();
+    }
+
+    async enforcePreset(name, enabled = true) {
+        const res = await fetch(`/api/presets/${encodeURIComponent(name)}/enforce`, {
+            method: enabled ? 'POST' : 'DELETE'
+        });
+        if (!res.ok) throw new Error(`Failed to change preset enforcement (${res.status})`);
+    }
+
+    // `xml` is the text of a qpwgraph patchbay file
+    async importPreset(xml, name = null) {
+        const query = name ? `?name=${encodeURIComponent(name)}` : '';
+        const res = await fetch(`/api/presets/import${query}`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/xml' },
+            body: xml
+        });
+        if (!res.ok) throw new Error(`Failed to import patchbay (${res.status})`);
+        return res.json();
+    }
+
+    presetExportUrl(name) {
+        return `/api/presets/${encodeURIComponent(name)}/export`;
+    }
+
+    async getRules() {
+        const res = await fetch('/api/rules');
+        return res.json();
+    }
+
+    async getRuleLog() {
+        const res = await fetch('/api/rules/log');
+        return res.json();
+    }
+
+    // Creates a rule, or replaces it when `id` is given
+    async saveRule(rule, id = null) {
+        const res = await fetch(id === null ? '/api/rules' : `/api/rules/${id}`, {
+            method: id === null ? 'POST' : 'PUT',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify(rule)
+        });
+        if (!res.ok) throw new Error(`Failed to save rule (${res.status})`);
+        return res.json();
+    }
+
+    async deleteRule(id) {
+        await fetch(`/api/rules/${id}`, { method: 'DELETE' });
+    }
 }
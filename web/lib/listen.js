@@ -0,0 +1,79 @@
+// Plays a node's audio streamed from /api/listen/:id through Web Audio.
+const MAX_LATENCY = 0.5; // seconds queued before chunks are dropped
+const START_DELAY = 0.1;
+
+export class NodeListener {
+    constructor(nodeId, { bitrate = 768, channels = 2 } = {}) {
+        this.nodeId = nodeId;
+        this.bitrate = bitrate;
+        this.channels = channels;
+        this.ws = null;
+        this.ctx = null;
+        this.format = null;
+        this.nextTime = 0;
+        this.onclose = null;
+    }
+
+    start() {
+        // Must be created from a user gesture on mobile browsers
+        this.ctx = new AudioContext();
+        const proto = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
+        const query = `bitrate=${this.bitrate}&channels=${this.channels}`;
+        this.ws = new WebSocket(`${proto}//${window.location.host}/api/listen/${this.nodeId}?${query}`);
+        this.ws.binaryType = 'arraybuffer';
+
+        this.ws.onmessage = (event) => {
+            if (typeof event.data === 'string') {
+                this.format = JSON.parse(event.data);
+                return;
+            }
+            if (this.format) this.enqueue(event.data);
+        };
+
+        this.ws.onclose = () => {
+            this.teardown();
+            if (this.onclose) this.onclose();
+        };
+    }
+
+    stop() {
+        if (this.ws) this.ws.close();
+        this.teardown();
+    }
+
+    teardown() {
+        if (this.ctx) {
+            this.ctx.close();
+            this.ctx = null;
+        }
+    }
+
+    enqueue(data) {
+        if (!this.ctx) return;
+        const { channels, sample_rate } = this.format;
+        const samples = new Int16Array(data);
+        const frames = samples.length / channels;
+        if (frames === 0) return;
+
+        const now = this.ctx.currentTime;
+        if (this.nextTime < now) {
+            this.nextTime = now + START_DELAY;
+        } else if (this.nextTime - now > MAX_LATENCY) {
+            return;
+        }
+
+        const buffer = this.ctx.createBuffer(channels, frames, sample_rate);
+        for (let c = 0; c < channels; c++) {
+            const out = buffer.getChannelData(c);
+            for (let i = 0; i < frames; i++) {
+                out[i] = samples[i * channels + c] / 32768;
+            }
+        }
+
+        const source = this.ctx.createBufferSource();
+        source.buffer = buffer;
+        source.connect(this.ctx.destination);
+        source.start(this.nextTime);
+        this.nextTime += buffer.duration;
+    }
+}
@@ -0,0 +1,66 @@
+// Sends the browser microphone to /api/mic, where it appears as a PipeWire source.
+const BLOCK_SIZE = 1024;
+
+export class BrowserMic {
+    constructor(name = 'Browser Microphone') {
+        this.name = name;
+        this.ws = null;
+        this.ctx = null;
+        this.stream = null;
+        this.processor = null;
+        this.onclose = null;
+    }
+
+    async start() {
+        if (!navigator.mediaDevices?.getUserMedia) {
+            throw new Error('Microphone access requires HTTPS or localhost');
+        }
+        this.stream = await navigator.mediaDevices.getUserMedia({ audio: true });
+        this.ctx = new AudioContext();
+
+        // The server resamples, so send at whatever rate the browser runs at
+        const proto = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
+        const query = `rate=${this.ctx.sampleRate}&channels=1&name=${encodeURIComponent(this.name)}`;
+        this.ws = new WebSocket(`${proto}//${window.location.host}/api/mic?${query}`);
+        this.ws.binaryType = 'arraybuffer';
+        this.ws.onclose = () => {
+            this.teardown();
+            if (this.onclose) this.onclose();
+        };
+
+        const input = this.ctx.createMediaStreamSource(this.stream);
+        this.processor = this.ctx.createScriptProcessor(BLOCK_SIZE, 1, 1);
+        this.processor.onaudioprocess = (e) => {
+            if (this.ws?.readyState !== WebSocket.OPEN) return;
+            const samples = e.inputBuffer.getChannelData(0);
+            const pcm = new Int16Array(samples.length);
+            for (let i = 0; i < samples.length; i++) {
+                pcm[i] = Math.max(-1, Math.min(1, samples[i])) * 32767;
+            }
+            this.ws.send(pcm.buffer);
+        };
+        input.connect(this.processor);
+        // ScriptProcessor only runs while connected to the graph; its output stays silent
+        this.processor.connect(this.ctx.destination);
+    }
+
+    stop() {
+        if (this.ws) this.ws.close();
+        this.teardown();
+    }
+
+    teardown() {
+        if (this.processor) {
+            this.processor.disconnect();
+            this.processor = null;
+        }
+        if (this.stream) {
+            this.stream.getTracks().forEach(t => t.stop());
+            this.stream = null;
+        }
+        if (this.ctx) {
+            this.ctx.close();
+            this.ctx = null;
+        }
+    }
+}
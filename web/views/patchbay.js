@@ -22,9 +22,11 @@ export class PatchbayView {
 
     async loadGraph() {
         try {
-            const graph = await this.api.getGraph();
+            // Refetched with the graph so new nodes get a server-side position
+            const [graph, layout] = await Promise.all([this.api.getGraph(), this.api.getLayout()]);
             const canvas = this.element.querySelector('rete-graph');
             if (canvas) {
+                canvas.setLayout(layout);
                 canvas.setGraph(graph);
             }
         } catch (e) {
@@ -44,6 +46,14 @@ export class PatchbayView {
         this.api.on('PortRemoved', refresh);
         this.api.on('LinkAdded', refresh);
         this.api.on('LinkRemoved', refresh);
+        this.api.on('LayoutChanged', (changes) => {
+            const canvas = this.element.querySelector('rete-graph');
+            if (canvas) canvas.applyLayout(changes);
+        });
+        this.api.on('Snapshot', ({ graph }) => {
+            const canvas = this.element.querySelector('rete-graph');
+            if (canvas) canvas.setGraph(graph);
+        });
     }
 
     setupInteraction() {
@@ -72,5 +82,14 @@ export class PatchbayView {
                 console.error('Failed to delete link:', err);
             }
         });
+
+        canvas.addEventListener('node-moved', async (e) => {
+            const { name, x, y } = e.detail;
+            try {
+                await this.api.saveLayout({ [name]: { x, y } });
+            } catch (err) {
+                console.error('Failed to save layout:', err);
+            }
+        });
     }
 }
@@ -1,9 +1,12 @@
+import { BrowserMic } from '../lib/mic.js';
+
 export class SetupView {
     constructor(api) {
         this.api = api;
         this.element = null;
         this.logBuffer = ["Console initialized..."];
         this.maxLogs = 500;
+        this.mic = null;
         
         this.api.on('Log', (msg) => {
             this.addLog(msg);
@@ -15,6 +18,11 @@ export class SetupView {
         this.element.className = 'setup-view';
         
         this.element.innerHTML = `
+            <div class="mic-panel">
+                <h3>Microphone</h3>
+                <button id="mic-toggle"></button>
+                <span id="mic-status" class="mic-status"></span>
+            </div>
             <div class="console-panel">
                 <h3>System Console</h3>
                 <div id="log-console" class="console-output"></div>
@@ -28,13 +36,33 @@ export class SetupView {
                     box-sizing: border-box;
                     color: #eee;
                 }
+                .mic-panel {
+                    margin-bottom: 12px;
+                }
+                .mic-panel button {
+                    background: none;
+                    border: 1px solid #888;
+                    color: #eee;
+                    padding: 4px 10px;
+                    cursor: pointer;
+                }
+                .mic-panel button.active {
+                    border-color: #e53935;
+                    color: #e53935;
+                }
+                .mic-status {
+                    margin-left: 8px;
+                    font-size: 11px;
+                    color: #888;
+                }
                 .console-panel {
                     flex: 1;
                     display: flex;
                     flex-direction: column;
                     min-height: 0;
                 }
-                .console-panel h3 {
+                .console-panel h3,
+                .mic-panel h3 {
                     margin-top: 0;
                     margin-bottom: 8px;
                     font-size: 14px;
@@ -73,9 +101,46 @@ export class SetupView {
         });
         consoleEl.scrollTop = consoleEl.scrollHeight;
 
+        this.element.querySelector('#mic-toggle').addEventListener('click', () => this.toggleMic());
+        this.updateMic();
+
         return this.element;
     }
 
+    async toggleMic() {
+        if (this.mic) {
+            this.mic.stop();
+            this.mic = null;
+            this.updateMic();
+            return;
+        }
+
+        const mic = new BrowserMic();
+        mic.onclose = () => {
+            if (this.mic !== mic) return;
+            this.mic = null;
+            this.updateMic();
+        };
+        try {
+            this.mic = mic;
+            this.updateMic();
+            await mic.start();
+        } catch (e) {
+            mic.stop();
+            this.mic = null;
+            this.updateMic(e.message);
+        }
+    }
+
+    updateMic(error) {
+        const btn = this.element?.querySelector('#mic-toggle');
+        const status = this.element?.querySelector('#mic-status');
+        if (!btn) return;
+        btn.classList.toggle('active', !!this.mic);
+        btn.textContent = this.mic ? 'Stop microphone' : 'Use this device as microphone';
+        status.textContent = error || (this.mic ? 'Streaming to a PipeWire source' : '');
+    }
+
     addLog(msg) {
         this.logBuffer.push(msg);
         if (this.logBuffer.length > this.maxLogs) {
@@ -1,4 +1,5 @@
 import '../components/slider.js';
+import { NodeListener } from '../lib/listen.js';
 
 export class VolumeView {
     constructor(api) {
@@ -8,6 +9,9 @@ export class VolumeView {
         this.showSelectedOnly = false;
         this.isVertical = false; 
         this.devicesCache = [];
+        this.recordings = new Map(); // node id -> recording info
+        this.playbacks = new Map(); // node id -> playback info
+        this.listener = null; // at most one node is monitored at a time
     }
 
     render() {
@@ -66,6 +70,30 @@ export class VolumeView {
                 .controls {
                     width: 100%;
                 }
+                .rec-btn,
+                .test-btn,
+                .listen-btn,
+                .boost-btn,
+                .mute-btn {
+                    background: none;
+                    border: 1px solid var(--secondary-text);
+                    color: var(--secondary-text);
+                    font-size: 11px;
+                    padding: 2px 6px;
+                    cursor: pointer;
+                    white-space: nowrap;
+                }
+                .rec-btn.recording,
+                .mute-btn.muted {
+                    border-color: #e53935;
+                    color: #e53935;
+                }
+                .listen-btn.listening,
+                .boost-btn.boosted,
+                .test-btn.playing {
+                    border-color: var(--primary);
+                    color: var(--primary);
+                }
 
                 /* Vertical Layout (Landscape Mixer) */
                 .device-list-container.vertical-layout {
@@ -126,7 +154,12 @@ export class VolumeView {
                     overflow: visible;
                     line-height: 1.1;
                 }
-                .vertical-layout .meta {
+                .vertical-layout .meta,
+                .vertical-layout .rec-btn,
+                .vertical-layout .test-btn,
+                .vertical-layout .listen-btn,
+                .vertical-layout .boost-btn,
+                .vertical-layout .mute-btn {
                     display: none; 
                 }
                 .vertical-layout .controls {
@@ -205,10 +238,16 @@ export class VolumeView {
                     <h3>${device.description}</h3>
                     <p class="meta">${device.name}</p>
                 </div>
+                ${device.device_type === 'Sink' ? '<button class="test-btn" title="Speaker test">TEST</button>' : ''}
+                <button class="boost-btn${device.boost ? ' boosted' : ''}" title="Allow volume above 100%">BOOST</button>
+                <button class="mute-btn${device.muted ? ' muted' : ''}" title="Mute">MUTE</button>
+                <button class="listen-btn" title="Listen in browser">LISTEN</button>
+                <button class="rec-btn" title="Record">● REC</button>
             </div>
             <div class="controls">
                 <volume-slider 
                     value="${volume}" 
+                    max="${device.boost ? 150 : 100}"
                     data-id="${device.id}"
                     ${verticalAttr}>
                 </volume-slider>
@@ -224,6 +263,60 @@ export class VolumeView {
             }
         });
 
+        el.querySelector('.boost-btn').addEventListener('click', async () => {
+            try {
+                const current = this.devicesCache.find(d => d.id === device.id) ?? device;
+                await this.api.setBoost(device.id, !current.boost);
+            } catch (err) {
+                console.error('Failed to toggle boost:', err);
+            }
+        });
+
+        el.querySelector('.mute-btn').addEventListener('click', async () => {
+            try {
+                await this.api.setMute(device.id);
+            } catch (err) {
+                console.error('Failed to toggle mute:', err);
+            }
+        });
+
+        const recBtn = el.querySelector('.rec-btn');
+        this.updateRecordButton(recBtn, this.recordings.get(device.id));
+        recBtn.addEventListener('click', async () => {
+            const active = this.recordings.get(device.id);
+            try {
+                if (active) {
+                    await this.api.stopRecording(active.id);
+                } else {
+                    await this.api.startRecording(device.id);
+                }
+            } catch (err) {
+                console.error('Failed to toggle recording:', err);
+            }
+        });
+
+        const testBtn = el.querySelector('.test-btn');
+        if (testBtn) {
+            testBtn.classList.toggle('playing', this.playbacks.has(device.id));
+            testBtn.addEventListener('click', async () => {
+                const active = this.playbacks.get(device.id);
+                try {
+                    if (active) {
+                        await this.api.stopPlayback(active.id);
+                    } else {
+                        const channels = device.channels.length || 2;
+                        await this.api.startPlayback(device.id, { kind: 'speaker_sweep' }, { channels });
+                    }
+                } catch (err) {
+                    console.error('Failed to toggle test signal:', err);
+                }
+            });
+        }
+
+        const listenBtn = el.querySelector('.listen-btn');
+        listenBtn.classList.toggle('listening', this.listener?.nodeId === device.id);
+        listenBtn.addEventListener('click', () => this.toggleListen(device.id));
+
         const checkbox = el.querySelector('.device-select');
         checkbox.addEventListener('change', (e) => {
             if (e.target.checked) {
@@ -239,9 +332,47 @@ export class VolumeView {
         return el;
     }
 
+    toggleListen(nodeId) {
+        const previous = this.listener;
+        if (previous) {
+            previous.onclose = null;
+            previous.stop();
+            this.listener = null;
+            this.element.querySelector(`#device-${previous.nodeId} .listen-btn`)?.classList.remove('listening');
+            if (previous.nodeId === nodeId) return;
+        }
+
+        const listener = new NodeListener(nodeId);
+        listener.onclose = () => {
+            if (this.listener !== listener) return;
+            this.listener = null;
+            this.element.querySelector(`#device-${nodeId} .listen-btn`)?.classList.remove('listening');
+        };
+        listener.start();
+        this.listener = listener;
+        this.element.querySelector(`#device-${nodeId} .listen-btn`)?.classList.add('listening');
+    }
+
+    updateRecordButton(btn, recording) {
+        if (!btn) return;
+        btn.classList.toggle('recording', !!recording);
+        if (recording) {
+            const secs = Math.floor(recording.elapsed_ms / 1000);
+            const mm = String(Math.floor(secs / 60)).padStart(2, '0');
+            const ss = String(secs % 60).padStart(2, '0');
+            btn.textContent = `■ ${mm}:${ss}`;
+        } else {
+            btn.textContent = '● REC';
+        }
+    }
+
     async loadDevices() {
         try {
             this.devicesCache = await this.api.getDevices();
+            const { active } = await this.api.getRecordings();
+            this.recordings = new Map(active.map(r => [r.node_id, r]));
+            const playback = await this.api.getPlayback();
+            this.playbacks = new Map(playback.active.map(p => [p.node_id, p]));
             this.reRenderAll();
         } catch (e) {
             const container = this.element.querySelector('#device-list');
@@ -250,12 +381,20 @@ export class VolumeView {
     }
 
     setupRealtime() {
+        this.api.on('Snapshot', ({ devices }) => {
+            this.devicesCache = devices;
+            this.reRenderAll();
+        });
+
         this.api.on('DeviceAdded', (device) => {
             const idx = this.devicesCache.findIndex(d => d.id === device.id);
             if (idx >= 0) {
                 this.devicesCache[idx] = device;
                 const slider = this.element.querySelector(`#device-${device.id} volume-slider`);
-                if (slider) slider.value = Math.round(device.channels[0]?.volume * 100 || 100);
+                if (slider) {
+                    slider.setAttribute('max', device.boost ? 150 : 100);
+                    slider.value = Math.round(device.channels[0]?.volume * 100 || 100);
+                }
             } else {
                 this.devicesCache.push(device);
                 const container = this.element.querySelector('#device-list');
@@ -287,5 +426,53 @@ export class VolumeView {
                 slider.syncFromServer(Math.round(volume * 100), timestamp);
             }
         });
+
+        this.api.on('MuteChanged', ({ id, muted }) => {
+            const device = this.devicesCache.find(d => d.id === id);
+            if (device) device.muted = muted;
+            this.element.querySelector(`#device-${id} .mute-btn`)?.classList.toggle('muted', muted);
+        });
+
+        this.api.on('BoostChanged', ({ id, boost }) => {
+            const device = this.devicesCache.find(d => d.id === id);
+            if (device) device.boost = boost;
+            this.element.querySelector(`#device-${id} .boost-btn`)?.classList.toggle('boosted', boost);
+            this.element.querySelector(`#device-${id} volume-slider`)?.setAttribute('max', boost ? 150 : 100);
+        });
+
+        this.api.on('RecordingStarted', (info) => {
+            this.recordings.set(info.node_id, info);
+            this.updateRecordButton(this.element.querySelector(`#device-${info.node_id} .rec-btn`), info);
+        });
+
+        this.api.on('RecordingProgress', ({ id, elapsed_ms, bytes }) => {
+            for (const info of this.recordings.values()) {
+                if (info.id !== id) continue;
+                info.elapsed_ms = elapsed_ms;
+                info.bytes = bytes;
+                this.updateRecordButton(this.element.querySelector(`#device-${info.node_id} .rec-btn`), info);
+            }
+        });
+
+        this.api.on('RecordingStopped', ({ id }) => {
+            for (const [nodeId, info] of this.recordings) {
+                if (info.id !== id) continue;
+                this.recordings.delete(nodeId);
+                this.updateRecordButton(this.element.querySelector(`#device-${nodeId} .rec-btn`), null);
+            }
+        });
+
+        this.api.on('PlaybackStarted', (info) => {
+            this.playbacks.set(info.node_id, info);
+            this.element.querySelector(`#device-${info.node_id} .test-btn`)?.classList.add('playing');
+        });
+
+        this.api.on('PlaybackStopped', ({ id }) => {
+            for (const [nodeId, info] of this.playbacks) {
+                if (info.id !== id) continue;
+                this.playbacks.delete(nodeId);
+                this.element.querySelector(`#device-${nodeId} .test-btn`)?.classList.remove('playing');
+            }
+        });
     }
 }